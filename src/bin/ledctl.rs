@@ -0,0 +1,99 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    process,
+};
+
+use clap::{Parser, Subcommand};
+
+/// Controls a running rasp-pi-setup over its Unix socket.
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Socket the daemon listens on.
+    #[arg(short, long, default_value = "/tmp/rasp-pi-setup.sock")]
+    socket: PathBuf,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the available effects.
+    Effects,
+    /// Print the current effect, parameters and brightness as JSON.
+    Status,
+    /// Print the measured frame rate.
+    Fps,
+    /// Switch to another effect.
+    Effect { name: String },
+    /// Set a parameter of the current effect.
+    Param { name: String, value: f32 },
+    /// Set the global brightness, 0-255.
+    Brightness { brightness: u8 },
+    /// Light the strip again after `off`.
+    On,
+    /// Blank the strip without stopping the daemon.
+    #[command(alias = "blackout")]
+    Off,
+    /// Freeze the effect on its current frame.
+    Pause,
+    /// Unfreeze the effect.
+    Resume,
+}
+
+impl Command {
+    /// The request line for this command.
+    fn request(&self) -> String {
+        match self {
+            Command::Effects => "effects".into(),
+            Command::Status => "status".into(),
+            Command::Fps => "fps".into(),
+            Command::Effect { name } => format!("effect {}", name),
+            Command::Param { name, value } => format!("param {} {}", name, value),
+            Command::Brightness { brightness } => format!("brightness {}", brightness),
+            Command::On => "on".into(),
+            Command::Off => "off".into(),
+            Command::Pause => "pause".into(),
+            Command::Resume => "resume".into(),
+        }
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    match send(&cli) {
+        Ok(Some(value)) => println!("{}", value),
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Sends one request and returns whatever value came back with `ok`.
+fn send(cli: &Cli) -> Result<Option<String>, String> {
+    let mut stream = UnixStream::connect(&cli.socket).map_err(|e| {
+        format!(
+            "failed to connect to {}: {} (is `run --socket` running?)",
+            cli.socket.display(),
+            e
+        )
+    })?;
+    writeln!(stream, "{}", cli.command.request()).map_err(|e| e.to_string())?;
+
+    let mut reply = String::new();
+    BufReader::new(stream)
+        .read_line(&mut reply)
+        .map_err(|e| e.to_string())?;
+    let reply = reply.trim_end();
+    match reply.split_once(' ').unwrap_or((reply, "")) {
+        ("ok", "") => Ok(None),
+        ("ok", value) => Ok(Some(value.to_string())),
+        ("err", message) => Err(message.to_string()),
+        _ => Err(format!("unexpected reply `{}`", reply)),
+    }
+}
@@ -0,0 +1,209 @@
+use std::{net::SocketAddr, path::PathBuf};
+
+use chrono::{Local, NaiveDateTime, NaiveTime};
+use clap::{Args, Parser, Subcommand};
+
+use crate::output::{ClampMode, ColorOrder, StripType};
+use crate::transition::TransitionCurve;
+
+/// Renders sled effects onto LED strips.
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Render an effect to the configured output until interrupted.
+    Run(RunArgs),
+    /// Render an effect in the live terminal visualizer.
+    Preview(RunArgs),
+    /// Print the available effects and the parameters each one takes.
+    ListEffects,
+    /// Parse a layout file and report what it describes.
+    ValidateLayout {
+        /// Layout file to check.
+        #[arg(short, long, default_value = "./config.yap")]
+        config: PathBuf,
+    },
+}
+
+/// Options for rendering. Anything given here overrides the app config file,
+/// which in turn overrides the built-in defaults.
+#[derive(Args)]
+pub struct RunArgs {
+    /// App config file. `./app.toml` is used if it exists.
+    #[arg(long)]
+    pub app_config: Option<PathBuf>,
+
+    /// Sled layout file (.toml or .yap). [default: ./config.yap]
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+
+    /// Reload the layout file whenever it changes.
+    #[arg(short, long, overrides_with = "no_watch_layout")]
+    pub watch_layout: bool,
+
+    /// Don't reload the layout file, even if the app config says to.
+    #[arg(long, overrides_with = "watch_layout")]
+    pub no_watch_layout: bool,
+
+    /// Effect to start with. [default: ripples]
+    #[arg(short, long)]
+    pub effect: Option<String>,
+
+    /// Sets a parameter of `--effect`, e.g. `--param max_ripples=20`.
+    /// May be repeated; `list-effects` shows what each effect accepts. Not
+    /// allowed alongside a playlist, which picks the effects itself; pass
+    /// `--no-playlist` or use `[effects.<name>]` in the app config instead.
+    #[arg(short, long = "param", value_name = "NAME=VALUE", value_parser = parse_param)]
+    pub params: Vec<(String, f32)>,
+
+    /// Where frames go: `ws281x`, `null`, `record:<path>`,
+    /// `artnet:<host>[:<port>][/<universe>]`, `ddp:<host>[:<port>]`, or `ddp`
+    /// for the controllers in the app config.
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    /// Number of physical LEDs, when it differs from the layout.
+    #[arg(long)]
+    pub leds: Option<usize>,
+
+    /// Color order and chipset of the ws281x strip. [default: ws2811-gbr]
+    #[arg(long, value_enum)]
+    pub strip_type: Option<StripType>,
+
+    /// GPIO pin driving the ws281x strip. [default: 18]
+    #[arg(long)]
+    pub pin: Option<i32>,
+
+    /// Global brightness, 0-255. [default: 255]
+    #[arg(short, long)]
+    pub brightness: Option<u8>,
+
+    /// Gamma exponent applied to every channel. Around 2.2 gives perceptually
+    /// even fades; 1.0 disables it. [default: 1.0]
+    #[arg(long)]
+    pub gamma: Option<f32>,
+
+    /// Red, green and blue multipliers for tinted strips, e.g. `1,0.85,0.7`.
+    #[arg(long, value_parser = parse_white_balance)]
+    pub white_balance: Option<[f32; 3]>,
+
+    /// How over-bright colors from effects are brought back into range.
+    /// [default: clip]
+    #[arg(long, value_enum)]
+    pub clamp: Option<ClampMode>,
+
+    /// Channel order sent to the output, for strips the output itself can't
+    /// reorder. [default: rgb]
+    #[arg(long, value_enum)]
+    pub color_order: Option<ColorOrder>,
+
+    /// Power supply budget in amps. Frames estimated to draw more are dimmed.
+    #[arg(long)]
+    pub max_amps: Option<f32>,
+
+    /// Estimated draw of one LED channel at full brightness, in milliamps.
+    /// [default: 20]
+    #[arg(long)]
+    pub ma_per_channel: Option<f32>,
+
+    /// Estimated draw of one unlit LED, in milliamps. [default: 1]
+    #[arg(long)]
+    pub idle_ma: Option<f32>,
+
+    /// How switching effects blends one into the next. [default: ease]
+    #[arg(long, value_enum)]
+    pub transition: Option<TransitionCurve>,
+
+    /// Length of effect transitions in seconds; 0 cuts instantly.
+    /// [default: 1]
+    #[arg(long)]
+    pub transition_duration: Option<f32>,
+
+    /// Ignore the playlist in the app config. With a playlist, `run` starts
+    /// on its first entry rather than `--effect`.
+    #[arg(long)]
+    pub no_playlist: bool,
+
+    /// Pretend the schedule's clock starts at this time, as
+    /// `YYYY-MM-DDTHH:MM` or just `HH:MM` for today. It runs on from there.
+    #[arg(long, value_parser = parse_now)]
+    pub now: Option<NaiveDateTime>,
+
+    /// Serve the HTTP control API. Listens on 127.0.0.1:8080 unless given
+    /// another address; use 0.0.0.0:<port> to reach it from the network.
+    #[arg(long, value_name = "ADDR", num_args = 0..=1, default_missing_value = "127.0.0.1:8080")]
+    pub http: Option<SocketAddr>,
+
+    /// Listen for `ledctl` on a Unix socket, at /tmp/rasp-pi-setup.sock
+    /// unless given another path.
+    #[arg(long, value_name = "PATH", num_args = 0..=1, default_missing_value = "/tmp/rasp-pi-setup.sock")]
+    pub socket: Option<PathBuf>,
+
+    /// Show E1.31 (sACN) data from a lighting console in place of the effect
+    /// while it arrives. `[sacn]` in the app config sets the universe.
+    #[arg(long, overrides_with = "no_sacn")]
+    pub sacn: bool,
+
+    /// Don't listen for sACN, even with `[sacn]` in the app config.
+    #[arg(long, overrides_with = "sacn")]
+    pub no_sacn: bool,
+
+    /// Likewise for Art-Net, set up by `[artnet]` in the app config.
+    #[arg(long, overrides_with = "no_artnet")]
+    pub artnet: bool,
+
+    /// Don't listen for Art-Net, even with `[artnet]` in the app config.
+    #[arg(long, overrides_with = "artnet")]
+    pub no_artnet: bool,
+
+    /// Target frame rate. Runs as fast as possible when omitted.
+    #[arg(long)]
+    pub fps: Option<f32>,
+
+    /// Advance effects by exactly 1/fps each frame instead of by wall-clock
+    /// time, so runs are reproducible.
+    #[arg(long, overrides_with = "no_fixed_step")]
+    pub fixed_step: bool,
+
+    /// Advance effects by wall-clock time, even if the app config says
+    /// otherwise.
+    #[arg(long, overrides_with = "fixed_step")]
+    pub no_fixed_step: bool,
+}
+
+fn parse_param(s: &str) -> Result<(String, f32), String> {
+    let (name, value) = s
+        .split_once('=')
+        .ok_or_else(|| "expected NAME=VALUE".to_string())?;
+    let value = value.trim().parse::<f32>().map_err(|e| e.to_string())?;
+    Ok((name.trim().to_string(), value))
+}
+
+fn parse_now(s: &str) -> Result<NaiveDateTime, String> {
+    ["%Y-%m-%dT%H:%M", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+        .or_else(|| {
+            let time = NaiveTime::parse_from_str(s, "%H:%M").ok()?;
+            Some(Local::now().date_naive().and_time(time))
+        })
+        .ok_or_else(|| "expected YYYY-MM-DDTHH:MM or HH:MM".to_string())
+}
+
+fn parse_white_balance(s: &str) -> Result<[f32; 3], String> {
+    let channels: Vec<f32> = s
+        .split(',')
+        .map(|c| c.trim().parse::<f32>())
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+
+    match channels[..] {
+        [r, g, b] => Ok([r, g, b]),
+        _ => Err("expected three comma-separated values".into()),
+    }
+}
@@ -0,0 +1,732 @@
+use std::{
+    collections::HashMap, fs, net::SocketAddr, ops::RangeInclusive, path::Path, path::PathBuf,
+    time::Duration,
+};
+
+use chrono::{NaiveTime, Weekday};
+use serde::Deserialize;
+
+use crate::cli::RunArgs;
+use crate::dmx::{DmxMapping, DmxSettings};
+use crate::effects::{Effect, Overrides, Registry};
+use crate::output::{
+    self, ClampMode, ColorOrder, ColorPipeline, DdpController, DdpSettings, GpioSettings, MapEntry,
+    MapLeds, PowerLimiter, StripType,
+};
+use crate::playlist::{Playlist, PlaylistEntry, PlaylistOrder};
+use crate::schedule::{Schedule, ScheduleRule};
+use crate::transition::{TransitionCurve, TransitionSettings};
+
+const DEFAULT_APP_CONFIG: &str = "./app.toml";
+
+/// The contents of the app config file. Every field is optional; anything
+/// left out falls back to the command line or a built-in default.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppConfig {
+    pub layout: Option<PathBuf>,
+    pub effect: Option<String>,
+    pub output: Option<String>,
+    pub fps: Option<f32>,
+    pub fixed_step: Option<bool>,
+    pub watch_layout: Option<bool>,
+    pub http: HttpConfig,
+    pub socket: SocketConfig,
+    /// E1.31 (sACN) input. Off when left out, unless `--sacn` is given.
+    pub sacn: Option<DmxConfig>,
+    /// Art-Net input. Off when left out, unless `--artnet` is given.
+    pub artnet: Option<DmxConfig>,
+    /// Home Assistant integration. Off when left out.
+    pub mqtt: Option<MqttConfig>,
+    pub strip: StripConfig,
+    pub ddp: DdpConfig,
+    /// `[[map]]` tables splitting the layout across several outputs, used
+    /// in place of `output` when present.
+    pub map: Vec<MapEntryConfig>,
+    pub color: ColorConfig,
+    pub power: PowerConfig,
+    pub transition: TransitionConfig,
+    pub playlist: PlaylistConfig,
+    /// `[[schedule]]` tables, checked in order.
+    pub schedule: Vec<ScheduleRuleConfig>,
+    /// Parameter overrides per effect, e.g. `[effects.ripples] max_ripples = 20`.
+    pub effects: HashMap<String, Overrides>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    /// Address for the HTTP control API, e.g. `"0.0.0.0:8080"` to allow
+    /// other devices on the network. Off when left out.
+    pub listen: Option<SocketAddr>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct SocketConfig {
+    /// Unix socket for `ledctl`, e.g. `"/run/rasp-pi-setup.sock"`. Off when
+    /// left out.
+    pub path: Option<PathBuf>,
+}
+
+/// Where to find the strip in a DMX-over-IP protocol's address space.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct DmxConfig {
+    /// Universe holding the first LED.
+    pub universe: Option<u16>,
+    /// Channel of the first LED's red level within it, from 1.
+    pub channel: Option<u16>,
+    /// Seconds without packets before going back to the effect.
+    pub timeout: Option<f32>,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(not(feature = "mqtt"), allow(dead_code))]
+pub struct MqttConfig {
+    /// Broker address.
+    pub host: String,
+    #[serde(default = "default_mqtt_port")]
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// MQTT client id and Home Assistant unique id.
+    #[serde(default = "default_node_id")]
+    pub node_id: String,
+    /// Name of the light in Home Assistant.
+    #[serde(default = "default_light_name")]
+    pub name: String,
+    /// Prefix for the state and command topics. [default: node_id]
+    pub topic: Option<String>,
+    #[serde(default = "default_discovery_prefix")]
+    pub discovery_prefix: String,
+}
+
+fn default_mqtt_port() -> u16 {
+    1883
+}
+
+fn default_node_id() -> String {
+    "rasp_pi_setup".into()
+}
+
+fn default_light_name() -> String {
+    "LED strip".into()
+}
+
+fn default_discovery_prefix() -> String {
+    "homeassistant".into()
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct StripConfig {
+    pub leds: Option<usize>,
+    pub pin: Option<i32>,
+    #[serde(rename = "type")]
+    pub strip_type: Option<StripType>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct DdpConfig {
+    /// Pixels per packet.
+    pub chunk: Option<usize>,
+    /// `[[ddp.controllers]]` tables, for `output = "ddp"`.
+    pub controllers: Vec<DdpControllerConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DdpControllerConfig {
+    pub host: String,
+    pub port: Option<u16>,
+    /// First pixel on the controller to write to.
+    pub offset: Option<usize>,
+    /// How many LEDs it takes; the rest if left out.
+    pub leds: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MapEntryConfig {
+    /// Output spec, as for `output`.
+    pub output: String,
+    /// A line segment of the layout, counting from 0.
+    pub segment: Option<usize>,
+    /// Or a range of LEDs: the first index, where the previous entry ended
+    /// if left out...
+    pub start: Option<usize>,
+    /// ...and how many, the rest of the layout if left out.
+    pub leds: Option<usize>,
+    /// First LED on the output to write to. Carries on from the previous
+    /// entry for the same output if left out.
+    pub offset: Option<usize>,
+    /// Physical LEDs to leave dark before this entry's first one.
+    pub skip: Option<usize>,
+    /// Whether the strip runs against the layout's direction.
+    pub reverse: Option<bool>,
+    /// Physical LEDs to keep dark, counted along the output from the first
+    /// one this entry writes to, after `skip`.
+    #[serde(default)]
+    pub dead: Vec<usize>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorConfig {
+    pub brightness: Option<u8>,
+    pub gamma: Option<f32>,
+    pub white_balance: Option<[f32; 3]>,
+    pub clamp: Option<ClampMode>,
+    pub order: Option<ColorOrder>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct PowerConfig {
+    pub max_amps: Option<f32>,
+    pub ma_per_channel: Option<f32>,
+    pub idle_ma: Option<f32>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct TransitionConfig {
+    pub curve: Option<TransitionCurve>,
+    /// Seconds.
+    pub duration: Option<f32>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlaylistConfig {
+    pub order: Option<PlaylistOrder>,
+    /// Seconds each entry plays for, unless the entry says otherwise.
+    pub duration: Option<f32>,
+    pub entries: Vec<PlaylistEntryConfig>,
+}
+
+/// One `[[playlist.entries]]` table.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlaylistEntryConfig {
+    pub effect: String,
+    pub duration: Option<f32>,
+    pub weight: Option<f32>,
+    pub transition: Option<TransitionCurve>,
+    pub transition_duration: Option<f32>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleRuleConfig {
+    /// Day names like `"mon"` or `"friday"`, or `"weekdays"` and
+    /// `"weekends"`. Every day if left out.
+    pub days: Option<Vec<String>>,
+    /// `"HH:MM"`.
+    pub from: String,
+    pub to: String,
+    pub effect: Option<String>,
+    pub brightness: Option<u8>,
+    pub off: Option<bool>,
+}
+
+impl AppConfig {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("invalid {}: {}", path.display(), e))
+    }
+}
+
+/// Everything needed to render, after layering the command line over the
+/// app config over the defaults.
+pub struct Settings {
+    pub layout: PathBuf,
+    pub effect: String,
+    pub effect_overrides: HashMap<String, Overrides>,
+    pub output: String,
+    pub leds: Option<usize>,
+    pub gpio: GpioSettings,
+    pub ddp: DdpSettings,
+    /// Where each part of the layout goes, if split across outputs.
+    pub map: Vec<MapEntry>,
+    pub pipeline: ColorPipeline,
+    pub limiter: PowerLimiter,
+    pub transition: TransitionSettings,
+    /// Effects to rotate through in `run`, if configured.
+    pub playlist: Option<Playlist>,
+    /// Time-of-day rules for `run`, if configured.
+    pub schedule: Option<Schedule>,
+    pub fps: Option<f32>,
+    pub fixed_step: bool,
+    pub watch_layout: bool,
+    pub http: Option<SocketAddr>,
+    pub socket: Option<PathBuf>,
+    /// E1.31 input that takes over from the effect while data arrives.
+    pub sacn: Option<DmxSettings>,
+    /// Art-Net input, likewise.
+    pub artnet: Option<DmxSettings>,
+    pub mqtt: Option<MqttConfig>,
+}
+
+impl Settings {
+    /// Loads the app config named by `args` (or the default one, if present)
+    /// and layers `args` on top of it.
+    pub fn resolve(args: &RunArgs, registry: &Registry) -> Result<Self, String> {
+        let file = match &args.app_config {
+            Some(path) => AppConfig::load(path)?,
+            None if Path::new(DEFAULT_APP_CONFIG).exists() => {
+                AppConfig::load(Path::new(DEFAULT_APP_CONFIG))?
+            }
+            None => AppConfig::default(),
+        };
+
+        let defaults = ColorPipeline::default();
+        let pipeline = ColorPipeline {
+            clamp: args.clamp.or(file.color.clamp).unwrap_or(defaults.clamp),
+            gamma: args.gamma.or(file.color.gamma).unwrap_or(defaults.gamma),
+            white_balance: args
+                .white_balance
+                .or(file.color.white_balance)
+                .unwrap_or(defaults.white_balance),
+            brightness: args
+                .brightness
+                .or(file.color.brightness)
+                .map(|b| b as f32 / 255.0)
+                .unwrap_or(defaults.brightness),
+            order: args
+                .color_order
+                .or(file.color.order)
+                .unwrap_or(defaults.order),
+        };
+
+        let defaults = PowerLimiter::default();
+        let limiter = PowerLimiter {
+            budget_ma: args
+                .max_amps
+                .or(file.power.max_amps)
+                .map(|amps| amps * 1000.0),
+            ma_per_channel: args
+                .ma_per_channel
+                .or(file.power.ma_per_channel)
+                .unwrap_or(defaults.ma_per_channel),
+            idle_ma_per_led: args
+                .idle_ma
+                .or(file.power.idle_ma)
+                .unwrap_or(defaults.idle_ma_per_led),
+        };
+
+        let defaults = TransitionSettings::default();
+        let transition = TransitionSettings {
+            curve: args
+                .transition
+                .or(file.transition.curve)
+                .unwrap_or(defaults.curve),
+            duration: match args.transition_duration.or(file.transition.duration) {
+                Some(secs) => seconds("transition duration", secs)?,
+                None => defaults.duration,
+            },
+        };
+
+        let playlist = if args.no_playlist || file.playlist.entries.is_empty() {
+            None
+        } else {
+            Some(resolve_playlist(file.playlist, &transition, registry)?)
+        };
+
+        let schedule = if file.schedule.is_empty() {
+            None
+        } else {
+            let rules = file
+                .schedule
+                .into_iter()
+                .map(|rule| resolve_schedule_rule(rule, registry))
+                .collect::<Result<_, _>>()?;
+            Some(Schedule::new(rules, args.now))
+        };
+
+        let sacn = if switch(args.sacn, args.no_sacn, file.sacn.is_some()) {
+            let config = file.sacn.unwrap_or_default();
+            Some(resolve_dmx("sacn", config, 1..=63999)?)
+        } else {
+            None
+        };
+        let artnet = if switch(args.artnet, args.no_artnet, file.artnet.is_some()) {
+            let config = file.artnet.unwrap_or_default();
+            Some(resolve_dmx("artnet", config, 0..=0x7fff)?)
+        } else {
+            None
+        };
+
+        let effect = args
+            .effect
+            .clone()
+            .or(file.effect)
+            .unwrap_or_else(|| "ripples".to_string());
+
+        // effect names are case-insensitive, so key the overrides by lowercase
+        // name before layering `--param` values for the starting effect on top
+        let mut effect_overrides: HashMap<String, Overrides> = HashMap::new();
+        for (name, overrides) in file.effects {
+            effect_overrides
+                .entry(name.to_lowercase())
+                .or_default()
+                .extend(overrides);
+        }
+        if !args.params.is_empty() {
+            // the playlist, not `--effect`, picks what `run` starts on
+            if playlist.is_some() {
+                return Err(
+                    "`--param` can't tell which effect to set while a playlist runs; \
+                     add `--no-playlist`, or set it under `[effects.<name>]` in the app config"
+                        .into(),
+                );
+            }
+            effect_overrides
+                .entry(effect.to_lowercase())
+                .or_default()
+                .extend(args.params.iter().cloned());
+        }
+
+        let settings = Settings {
+            layout: args
+                .config
+                .clone()
+                .or(file.layout)
+                .unwrap_or_else(|| PathBuf::from("./config.yap")),
+            effect,
+            effect_overrides,
+            output: args
+                .output
+                .clone()
+                .or(file.output)
+                .unwrap_or_else(|| output::default_spec().to_string()),
+            leds: args.leds.or(file.strip.leds),
+            gpio: GpioSettings {
+                pin: args.pin.or(file.strip.pin).unwrap_or(18),
+                strip_type: args
+                    .strip_type
+                    .or(file.strip.strip_type)
+                    .unwrap_or(StripType::Ws2811Gbr),
+            },
+            ddp: resolve_ddp(file.ddp)?,
+            map: file
+                .map
+                .into_iter()
+                .map(resolve_map_entry)
+                .collect::<Result<_, _>>()?,
+            pipeline,
+            limiter,
+            transition,
+            playlist,
+            schedule,
+            fps: args.fps.or(file.fps),
+            fixed_step: switch(
+                args.fixed_step,
+                args.no_fixed_step,
+                file.fixed_step.unwrap_or(false),
+            ),
+            watch_layout: switch(
+                args.watch_layout,
+                args.no_watch_layout,
+                file.watch_layout.unwrap_or(false),
+            ),
+            http: args.http.or(file.http.listen),
+            socket: args.socket.clone().or(file.socket.path),
+            sacn,
+            artnet,
+            mqtt: file.mqtt,
+        };
+
+        settings.validate(registry)?;
+        Ok(settings)
+    }
+
+    /// The parameter overrides configured for `effect`, if any.
+    pub fn overrides_for(&self, effect: &Effect) -> Option<&Overrides> {
+        self.effect_overrides.get(&effect.name.to_lowercase())
+    }
+
+    fn validate(&self, registry: &Registry) -> Result<(), String> {
+        for (name, overrides) in &self.effect_overrides {
+            let effect = registry
+                .get(name)
+                .ok_or_else(|| format!("[effects.{}] does not name a known effect", name))?;
+            effect
+                .check_overrides(overrides)
+                .map_err(|e| format!("[effects.{}]: {}", name, e))?;
+        }
+
+        if cfg!(not(feature = "mqtt")) && self.mqtt.is_some() {
+            return Err(
+                "[mqtt] is set but this build has no MQTT support; rebuild with \
+                 `--features mqtt`"
+                    .into(),
+            );
+        }
+        if let Some(fps) = self.fps {
+            if fps.is_nan() || fps <= 0.0 || fps.is_infinite() {
+                return Err(format!("fps must be a positive number, got {}", fps));
+            }
+            // the pacer sleeps 1/fps seconds a frame
+            if Duration::try_from_secs_f32(1.0 / fps).is_err() {
+                return Err(format!("fps is too low, got {}", fps));
+            }
+        }
+        if self.fixed_step && self.fps.is_none() {
+            return Err("fixed_step needs a target fps".into());
+        }
+        if self.leds == Some(0) {
+            return Err("leds must be at least 1".into());
+        }
+        if !self.pipeline.gamma.is_finite() || self.pipeline.gamma <= 0.0 {
+            return Err(format!(
+                "gamma must be a positive number, got {}",
+                self.pipeline.gamma
+            ));
+        }
+        if self
+            .pipeline
+            .white_balance
+            .iter()
+            .any(|c| !(0.0..=1.0).contains(c))
+        {
+            return Err("white_balance values must be between 0 and 1".into());
+        }
+        if let Some(budget) = self.limiter.budget_ma {
+            if !budget.is_finite() || budget <= 0.0 {
+                return Err("max_amps must be positive".into());
+            }
+        }
+        if !self.limiter.ma_per_channel.is_finite() || self.limiter.ma_per_channel <= 0.0 {
+            return Err(format!(
+                "ma_per_channel must be positive, got {}",
+                self.limiter.ma_per_channel
+            ));
+        }
+        if !self.limiter.idle_ma_per_led.is_finite() || self.limiter.idle_ma_per_led < 0.0 {
+            return Err(format!(
+                "idle_ma can't be negative, got {}",
+                self.limiter.idle_ma_per_led
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fn resolve_playlist(
+    config: PlaylistConfig,
+    transition: &TransitionSettings,
+    registry: &Registry,
+) -> Result<Playlist, String> {
+    let default_duration = seconds("playlist duration", config.duration.unwrap_or(60.0))?;
+    let order = config.order.unwrap_or(PlaylistOrder::Sequential);
+
+    let mut entries = Vec::new();
+    for entry in config.entries {
+        let effect = registry
+            .index_of(&entry.effect)
+            .ok_or_else(|| format!("playlist entry `{}` is not a known effect", entry.effect))?;
+        let duration = match entry.duration {
+            Some(secs) => seconds("playlist entry duration", secs)?,
+            None => default_duration,
+        };
+        if duration.is_zero() {
+            return Err(format!(
+                "playlist entry `{}` needs a duration above zero",
+                entry.effect
+            ));
+        }
+        let weight = entry.weight.unwrap_or(1.0);
+        if !weight.is_finite() || weight < 0.0 {
+            return Err(format!(
+                "playlist weights can't be negative, got {}",
+                weight
+            ));
+        }
+        let entry_transition = match (entry.transition, entry.transition_duration) {
+            (None, None) => None,
+            (curve, secs) => Some(TransitionSettings {
+                curve: curve.unwrap_or(transition.curve),
+                duration: match secs {
+                    Some(secs) => seconds("transition duration", secs)?,
+                    None => transition.duration,
+                },
+            }),
+        };
+
+        entries.push(PlaylistEntry {
+            effect,
+            duration,
+            weight,
+            transition: entry_transition,
+        });
+    }
+
+    if order == PlaylistOrder::Random && entries.iter().all(|entry| entry.weight == 0.0) {
+        return Err("a random playlist needs at least one entry with a weight above zero".into());
+    }
+    Ok(Playlist::new(entries, order))
+}
+
+fn resolve_ddp(config: DdpConfig) -> Result<DdpSettings, String> {
+    let chunk = config.chunk.unwrap_or(DdpSettings::default().chunk);
+    if !(1..=DdpSettings::MAX_CHUNK).contains(&chunk) {
+        return Err(format!(
+            "[ddp] chunk must be 1 to {} pixels, got {}",
+            DdpSettings::MAX_CHUNK,
+            chunk
+        ));
+    }
+
+    let mut controllers = Vec::new();
+    for controller in config.controllers {
+        if controller.leds == Some(0) {
+            return Err(format!(
+                "DDP controller {} needs at least 1 LED",
+                controller.host
+            ));
+        }
+        controllers.push(DdpController {
+            host: controller.host,
+            port: controller.port,
+            offset: controller.offset.unwrap_or(0),
+            leds: controller.leds,
+        });
+    }
+    Ok(DdpSettings { chunk, controllers })
+}
+
+fn resolve_map_entry(config: MapEntryConfig) -> Result<MapEntry, String> {
+    let leds = match (config.segment, config.start, config.leds) {
+        (Some(segment), None, None) => MapLeds::Segment(segment),
+        (Some(_), _, _) => {
+            return Err(format!(
+                "map entry for `{}` can have a segment or start and leds, not both",
+                config.output
+            ))
+        }
+        (None, _, Some(0)) => {
+            return Err(format!(
+                "map entry for `{}` needs at least 1 LED",
+                config.output
+            ))
+        }
+        (None, start, count) => MapLeds::Range { start, count },
+    };
+    Ok(MapEntry {
+        output: config.output,
+        leds,
+        offset: config.offset,
+        skip: config.skip.unwrap_or(0),
+        reverse: config.reverse.unwrap_or(false),
+        dead: config.dead,
+    })
+}
+
+/// Checks a DMX input table, defaulting to the first of `universes`.
+fn resolve_dmx(
+    table: &str,
+    config: DmxConfig,
+    universes: RangeInclusive<u16>,
+) -> Result<DmxSettings, String> {
+    let universe = config.universe.unwrap_or(*universes.start());
+    if !universes.contains(&universe) {
+        return Err(format!(
+            "[{}] universe must be {} to {}, got {}",
+            table,
+            universes.start(),
+            universes.end(),
+            universe
+        ));
+    }
+    // the first LED needs all three of its channels in the universe
+    let channel = config.channel.unwrap_or(1);
+    if !(1..=510).contains(&channel) {
+        return Err(format!(
+            "[{}] channel must be 1 to 510, got {}",
+            table, channel
+        ));
+    }
+    Ok(DmxSettings {
+        mapping: DmxMapping { universe, channel },
+        timeout: seconds(
+            &format!("[{}] timeout", table),
+            config.timeout.unwrap_or(2.5),
+        )?,
+    })
+}
+
+fn resolve_schedule_rule(
+    config: ScheduleRuleConfig,
+    registry: &Registry,
+) -> Result<ScheduleRule, String> {
+    let parse_time = |s: &str| {
+        NaiveTime::parse_from_str(s, "%H:%M")
+            .map_err(|_| format!("schedule times must look like \"22:30\", got \"{}\"", s))
+    };
+
+    let mut days = Vec::new();
+    for day in config.days.as_deref().unwrap_or(&["daily".to_string()]) {
+        match day.to_lowercase().as_str() {
+            "daily" => days.extend(WEEK),
+            "weekdays" => days.extend(&WEEK[..5]),
+            "weekends" => days.extend(&WEEK[5..]),
+            other => days.push(
+                other
+                    .parse::<Weekday>()
+                    .map_err(|_| format!("`{}` is not a day of the week", day))?,
+            ),
+        }
+    }
+
+    let effect = match &config.effect {
+        Some(name) => Some(
+            registry
+                .index_of(name)
+                .ok_or_else(|| format!("scheduled effect `{}` is not a known effect", name))?,
+        ),
+        None => None,
+    };
+
+    Ok(ScheduleRule {
+        days,
+        from: parse_time(&config.from)?,
+        to: parse_time(&config.to)?,
+        effect,
+        brightness: config.brightness.map(|b| b as f32 / 255.0),
+        off: config.off,
+    })
+}
+
+const WEEK: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+/// Resolves an on/off option from its `--flag` and `--no-flag` and the app
+/// config, in that order.
+fn switch(on: bool, off: bool, file: bool) -> bool {
+    on || (!off && file)
+}
+
+/// Converts a number of seconds from the config, rejecting negative values
+/// and ones too large to hold.
+fn seconds(what: &str, secs: f32) -> Result<Duration, String> {
+    if !secs.is_finite() || secs < 0.0 {
+        return Err(format!(
+            "{} must be zero or more seconds, got {}",
+            what, secs
+        ));
+    }
+    Duration::try_from_secs_f32(secs).map_err(|_| format!("{} is too long, got {}", what, secs))
+}
@@ -0,0 +1,277 @@
+use std::{net::SocketAddr, sync::Arc, thread};
+
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use super::{stream, Command, Controller};
+use crate::effects::{Effect, Overrides};
+
+/// Threads taking requests, so a command waiting on a slow frame doesn't hold
+/// up every other client.
+const WORKERS: usize = 4;
+
+/// A page that draws the live stream from `/api/stream` onto a canvas.
+const PREVIEW_PAGE: &str = include_str!("../../static/preview.html");
+
+/// Starts the HTTP API on background threads, returning the address it
+/// listens on.
+///
+/// - `GET /api/effects` lists every effect and its parameters.
+/// - `GET /api/status` reports the current effect, parameters and brightness.
+/// - `GET /api/frame` returns the last frame as `[r, g, b]` triples.
+/// - `POST /api/effect` switches effect, e.g. `{"name": "comet"}`.
+/// - `POST /api/params` sets parameters, e.g. `{"max_ripples": 20}`. If any
+///   of them is rejected, none are set.
+/// - `POST /api/brightness` sets brightness from 0 to 255, e.g.
+///   `{"brightness": 128}`.
+/// - `POST /api/on` and `POST /api/off` light and blank the strip.
+/// - `POST /api/pause` and `POST /api/resume` freeze and unfreeze the effect.
+/// - `GET /api/stream` is a WebSocket of live frames; see [`stream::serve`].
+/// - `GET /` is a browser preview built on that stream.
+pub fn serve(
+    addr: SocketAddr,
+    controller: Controller,
+    effects: Vec<Effect>,
+) -> Result<SocketAddr, String> {
+    let server = Server::http(addr).map_err(|e| format!("failed to listen on {}: {}", addr, e))?;
+    let addr = server.server_addr().to_ip().unwrap_or(addr);
+    println!("HTTP control listening on http://{}.", addr);
+
+    let server = Arc::new(server);
+    for _ in 0..WORKERS {
+        let server = server.clone();
+        let controller = controller.clone();
+        let effects = effects.clone();
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                handle(request, &controller, &effects);
+            }
+        });
+    }
+    Ok(addr)
+}
+
+fn handle(mut request: Request, controller: &Controller, effects: &[Effect]) {
+    if *request.method() == Method::Get {
+        match request.url() {
+            "/api/stream" => return stream::serve(request, controller.clone()),
+            "/" | "/index.html" => {
+                let content_type = Header::from_bytes("Content-Type", "text/html").unwrap();
+                let _ =
+                    request.respond(Response::from_string(PREVIEW_PAGE).with_header(content_type));
+                return;
+            }
+            _ => {}
+        }
+    }
+
+    let mut body = String::new();
+    let result = match request.as_reader().read_to_string(&mut body) {
+        Ok(_) => route(request.method(), request.url(), &body, controller, effects),
+        Err(e) => Err((400, e.to_string())),
+    };
+
+    let (code, value) = match result {
+        Ok(value) => (200, value),
+        Err((code, message)) => (code, json!({ "error": message })),
+    };
+    let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+    let response = Response::from_string(value.to_string())
+        .with_status_code(code)
+        .with_header(content_type);
+    // nothing to do if the client hung up
+    let _ = request.respond(response);
+}
+
+fn route(
+    method: &Method,
+    url: &str,
+    body: &str,
+    controller: &Controller,
+    effects: &[Effect],
+) -> Result<Value, (u16, String)> {
+    let path = url.split('?').next().unwrap_or(url);
+    let command = match (method, path) {
+        (Method::Get, "/api/effects") => return Ok(effects_json(effects)),
+        (Method::Get, "/api/status") => return Ok(json!(controller.status())),
+        (Method::Get, "/api/frame") => {
+            let colors = controller.frame();
+            return Ok(json!({ "leds": colors.len(), "colors": colors }));
+        }
+        (Method::Post, "/api/effect") => {
+            let name = parse(body)?
+                .get("name")
+                .and_then(Value::as_str)
+                .ok_or_else(|| (400, "expected {\"name\": \"<effect>\"}".to_string()))?
+                .to_string();
+            Command::SetEffect(name)
+        }
+        (Method::Post, "/api/params") => {
+            let body = parse(body)?;
+            let params = body
+                .as_object()
+                .ok_or_else(|| (400, "expected an object of parameter values".to_string()))?;
+            let params = params
+                .iter()
+                .map(|(name, value)| match value.as_f64() {
+                    Some(value) => Ok((name.clone(), value as f32)),
+                    None => Err((400, format!("`{}` must be a number", name))),
+                })
+                .collect::<Result<Overrides, _>>()?;
+            Command::SetParams(params)
+        }
+        (Method::Post, "/api/brightness") => {
+            let brightness = parse(body)?
+                .get("brightness")
+                .and_then(Value::as_u64)
+                .filter(|b| *b <= 255)
+                .ok_or_else(|| (400, "expected {\"brightness\": 0-255}".to_string()))?;
+            Command::SetBrightness(brightness as f32 / 255.0)
+        }
+        (Method::Post, "/api/on") => Command::SetOn(true),
+        (Method::Post, "/api/off") => Command::SetOn(false),
+        (Method::Post, "/api/pause") => Command::Pause,
+        (Method::Post, "/api/resume") => Command::Resume,
+        _ => return Err((404, format!("no route for {} {}", method, path))),
+    };
+
+    controller.send(command).map_err(|e| (400, e))?;
+    Ok(json!(controller.status()))
+}
+
+fn parse(body: &str) -> Result<Value, (u16, String)> {
+    serde_json::from_str(body).map_err(|e| (400, format!("invalid JSON: {}", e)))
+}
+
+fn effects_json(effects: &[Effect]) -> Value {
+    let effects: Vec<Value> = effects
+        .iter()
+        .map(|effect| {
+            json!({
+                "name": effect.name,
+                "description": effect.description,
+                "params": effect.params,
+            })
+        })
+        .collect();
+    json!(effects)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::control::remote;
+    use crate::effects::Registry;
+
+    fn start() -> SocketAddr {
+        let controller = remote::spawn_renderer();
+        let effects = Registry::builtin().iter().copied().collect();
+        serve("127.0.0.1:0".parse().unwrap(), controller, effects).unwrap()
+    }
+
+    fn call(addr: SocketAddr, method: &str, path: &str, body: &str) -> (u16, Value) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+             Content-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        let code = response[9..12].parse().unwrap();
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        (code, serde_json::from_str(body).unwrap())
+    }
+
+    #[test]
+    fn lists_effects() {
+        let addr = start();
+        let (code, effects) = call(addr, "GET", "/api/effects", "");
+        assert_eq!(code, 200);
+        let effects = effects.as_array().unwrap();
+        assert!(effects.iter().any(|effect| effect["name"] == "comet"));
+        assert!(effects.iter().all(|effect| effect["params"].is_array()));
+    }
+
+    #[test]
+    fn switches_effect() {
+        let addr = start();
+        let (code, status) = call(addr, "POST", "/api/effect", r#"{"name": "comet"}"#);
+        assert_eq!(code, 200);
+        assert_eq!(status["effect"], "comet");
+
+        let (code, error) = call(addr, "POST", "/api/effect", r#"{"name": "nope"}"#);
+        assert_eq!(code, 400);
+        assert!(error["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("unknown effect `nope`"));
+        let (code, _) = call(addr, "POST", "/api/effect", "{}");
+        assert_eq!(code, 400);
+    }
+
+    #[test]
+    fn sets_params_all_or_nothing() {
+        let addr = start();
+        let body = r#"{"max_ripples": 20, "feathering": 0.5}"#;
+        let (code, status) = call(addr, "POST", "/api/params", body);
+        assert_eq!(code, 200);
+        assert_eq!(status["params"]["max_ripples"], 20.0);
+        assert_eq!(status["params"]["feathering"], 0.5);
+
+        let body = r#"{"max_ripples": 5, "nope": 1}"#;
+        let (code, _) = call(addr, "POST", "/api/params", body);
+        assert_eq!(code, 400);
+        let (_, status) = call(addr, "GET", "/api/status", "");
+        assert_eq!(status["params"]["max_ripples"], 20.0);
+
+        let (code, _) = call(addr, "POST", "/api/params", r#"{"max_ripples": "x"}"#);
+        assert_eq!(code, 400);
+    }
+
+    #[test]
+    fn sets_brightness() {
+        let addr = start();
+        let (code, status) = call(addr, "POST", "/api/brightness", r#"{"brightness": 128}"#);
+        assert_eq!(code, 200);
+        assert_eq!(status["brightness"], 128);
+
+        let (code, _) = call(addr, "POST", "/api/brightness", r#"{"brightness": 256}"#);
+        assert_eq!(code, 400);
+    }
+
+    #[test]
+    fn returns_the_last_frame() {
+        let addr = start();
+        // give the render loop a moment to publish
+        thread::sleep(Duration::from_millis(50));
+        let (_, status) = call(addr, "GET", "/api/status", "");
+        let (code, frame) = call(addr, "GET", "/api/frame", "");
+        assert_eq!(code, 200);
+        assert_eq!(frame["leds"], status["leds"]);
+        let colors = frame["colors"].as_array().unwrap();
+        assert_eq!(json!(colors.len()), status["leds"]);
+        assert!(colors
+            .iter()
+            .all(|color| color.as_array().unwrap().len() == 3));
+    }
+
+    #[test]
+    fn unknown_routes_are_not_found() {
+        let addr = start();
+        let (code, _) = call(addr, "GET", "/api/nope", "");
+        assert_eq!(code, 404);
+    }
+}
@@ -0,0 +1,267 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    time::Duration,
+};
+
+use serde::Serialize;
+use sled::{color::Srgb, Vec2};
+
+use crate::effects::Overrides;
+use crate::output::to_rgb8;
+
+pub mod http;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+pub mod remote;
+pub mod socket;
+mod stream;
+
+/// Something a remote client asked the render loop to do.
+pub enum Command {
+    SetEffect(String),
+    /// Sets parameters of the current effect: all of them, or none if any is
+    /// unknown or out of range.
+    SetParams(Overrides),
+    /// Global brightness, 0 to 1.
+    SetBrightness(f32),
+    /// Turns the strip on, or blanks it without stopping the renderer.
+    SetOn(bool),
+    Pause,
+    Resume,
+}
+
+/// What the render loop says back: `Err` explains why it couldn't comply.
+type Reply = Result<(), String>;
+
+/// A request's [`Request::state`] before either side has claimed it.
+const PENDING: u8 = 0;
+/// The render loop has taken the request and will reply to it.
+const TAKEN: u8 = 1;
+/// The client gave up waiting, so the render loop must drop the request.
+const ABANDONED: u8 = 2;
+
+struct Request {
+    command: Command,
+    reply: Sender<Reply>,
+    /// Settles who got to the request first, so it's never applied after
+    /// its client was told it failed.
+    state: Arc<AtomicU8>,
+}
+
+impl Request {
+    /// Moves the request on from pending, returning whether this side won.
+    fn claim(state: &AtomicU8, to: u8) -> bool {
+        state
+            .compare_exchange(PENDING, to, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+}
+
+/// A snapshot of what the render loop is doing, for clients to read without
+/// waiting on it.
+#[derive(Clone, Serialize, Default)]
+pub struct Status {
+    pub effect: String,
+    pub params: BTreeMap<String, f32>,
+    /// 0-255, like the `brightness` setting.
+    pub brightness: u8,
+    /// False while the strip is blanked, by a client or the schedule.
+    pub on: bool,
+    pub paused: bool,
+    /// Measured frame rate, once there's been time to measure it.
+    pub fps: Option<f32>,
+    pub leds: usize,
+}
+
+#[derive(Default)]
+struct Published {
+    status: Status,
+    frame: Vec<[u8; 3]>,
+    /// Counts published frames, so streams can tell when there's a new one.
+    frame_number: u64,
+    positions: Vec<[f32; 2]>,
+    /// Counts layout changes, so streams know to resend positions.
+    layout_number: u64,
+}
+
+#[derive(Default)]
+struct Shared {
+    published: Mutex<Published>,
+    new_frame: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Published> {
+        self.published.lock().unwrap()
+    }
+}
+
+/// The client side: cheap to clone and hand to server threads.
+#[derive(Clone)]
+pub struct Controller {
+    requests: Sender<Request>,
+    shared: Arc<Shared>,
+}
+
+/// The render loop's side, which applies commands between frames and
+/// publishes what it drew.
+pub struct ControlHandle {
+    requests: Receiver<Request>,
+    shared: Arc<Shared>,
+    /// Results held back until the next publish, so a client that reads the
+    /// status after its command returns sees the change.
+    replies: Vec<(Sender<Reply>, Reply)>,
+}
+
+pub fn channel() -> (Controller, ControlHandle) {
+    let (requests, receiver) = mpsc::channel();
+    let shared = Arc::new(Shared::default());
+    (
+        Controller {
+            requests,
+            shared: shared.clone(),
+        },
+        ControlHandle {
+            requests: receiver,
+            shared,
+            replies: Vec::new(),
+        },
+    )
+}
+
+impl Controller {
+    /// Hands `command` to the render loop and waits for it to be applied.
+    /// If the render loop hasn't picked it up within 2 seconds, the command
+    /// is dropped and never applied.
+    pub fn send(&self, command: Command) -> Result<(), String> {
+        let (reply, response) = mpsc::channel();
+        let state = Arc::new(AtomicU8::new(PENDING));
+        let stopped = || "the renderer has stopped".to_string();
+        self.requests
+            .send(Request {
+                command,
+                reply,
+                state: state.clone(),
+            })
+            .map_err(|_| stopped())?;
+        match response.recv_timeout(Duration::from_secs(2)) {
+            Ok(reply) => reply,
+            Err(RecvTimeoutError::Disconnected) => Err(stopped()),
+            Err(RecvTimeoutError::Timeout) => {
+                if Request::claim(&state, ABANDONED) {
+                    return Err("the renderer didn't respond".into());
+                }
+                // it was taken just in time, so the reply is on its way
+                response.recv().map_err(|_| stopped())?
+            }
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        self.shared.lock().status.clone()
+    }
+
+    /// The most recent frame as the effect drew it, before color correction,
+    /// channel reordering and power limiting.
+    pub fn frame(&self) -> Vec<[u8; 3]> {
+        self.shared.lock().frame.clone()
+    }
+
+    /// Waits for a frame newer than `seen` and returns it with its number.
+    pub fn next_frame(&self, seen: u64) -> (u64, Vec<[u8; 3]>) {
+        let published = self
+            .shared
+            .new_frame
+            .wait_while(self.shared.lock(), |published| {
+                published.frame_number <= seen
+            })
+            .unwrap();
+        (published.frame_number, published.frame.clone())
+    }
+
+    /// Every LED's position, in index order, along with a number that
+    /// changes whenever the layout does.
+    pub fn layout(&self) -> (u64, Vec<[f32; 2]>) {
+        let published = self.shared.lock();
+        (published.layout_number, published.positions.clone())
+    }
+}
+
+impl ControlHandle {
+    /// Runs `handle` on every command that arrived since the last call,
+    /// skipping any whose client has given up. Clients hear back at the next
+    /// [`ControlHandle::publish`].
+    pub fn handle(&mut self, mut handle: impl FnMut(Command) -> Result<(), String>) {
+        while let Ok(request) = self.requests.try_recv() {
+            if !Request::claim(&request.state, TAKEN) {
+                continue;
+            }
+            let result = handle(request.command);
+            self.replies.push((request.reply, result));
+        }
+    }
+
+    pub fn publish(&mut self, status: Status, frame: &[Srgb]) {
+        {
+            let mut published = self.shared.lock();
+            published.status = status;
+            published.frame.clear();
+            published.frame.extend(frame.iter().map(to_rgb8));
+            published.frame_number += 1;
+        }
+        self.shared.new_frame.notify_all();
+        for (reply, result) in self.replies.drain(..) {
+            // the client may have gone away, which is fine
+            let _ = reply.send(result);
+        }
+    }
+
+    /// Publishes the LED positions of a newly mounted layout.
+    pub fn publish_layout(&mut self, positions: impl Iterator<Item = Vec2>) {
+        let mut published = self.shared.lock();
+        published.positions = positions.map(|pos| [pos.x, pos.y]).collect();
+        published.layout_number += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn commands_are_answered_at_the_next_publish() {
+        let (controller, mut control) = channel();
+        let sender = thread::spawn(move || controller.send(Command::Pause));
+        let mut handled = 0;
+        while handled == 0 {
+            thread::sleep(Duration::from_millis(5));
+            control.handle(|_| {
+                handled += 1;
+                Ok(())
+            });
+        }
+        control.publish(Status::default(), &[]);
+        assert_eq!(sender.join().unwrap(), Ok(()));
+    }
+
+    #[test]
+    fn a_command_that_timed_out_is_never_applied() {
+        let (controller, mut control) = channel();
+        let result = controller.send(Command::Pause);
+        assert_eq!(result, Err("the renderer didn't respond".to_string()));
+
+        let mut handled = 0;
+        control.handle(|_| {
+            handled += 1;
+            Ok(())
+        });
+        assert_eq!(handled, 0);
+    }
+}
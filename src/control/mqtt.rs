@@ -0,0 +1,269 @@
+use std::{
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+use rumqttc::{Client, Event, LastWill, MqttOptions, Packet, QoS};
+use serde_json::{json, Value};
+
+use super::{Command, Controller, Status};
+use crate::config::MqttConfig;
+
+/// Connects to the broker and exposes the strip to Home Assistant as a light
+/// using its JSON schema, with one light effect per sled effect.
+///
+/// Discovery goes to `<discovery_prefix>/light/<node_id>/config`; commands
+/// arrive on `<topic>/set` and state is published, retained, to `<topic>/state`.
+/// One background thread drives the connection, reconnecting on its own, and
+/// hands everything that might block to a second.
+pub fn connect(config: &MqttConfig, controller: Controller, effects: Vec<&'static str>) {
+    let node_id = config.node_id.clone();
+    let topic = config.topic.clone().unwrap_or_else(|| node_id.clone());
+    let availability_topic = format!("{}/availability", topic);
+    let command_topic = format!("{}/set", topic);
+    let state_topic = format!("{}/state", topic);
+
+    let mut options = MqttOptions::new(&node_id, &config.host, config.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    options.set_last_will(LastWill::new(
+        &availability_topic,
+        "offline",
+        QoS::AtLeastOnce,
+        true,
+    ));
+    if let Some(username) = &config.username {
+        options.set_credentials(username, config.password.clone().unwrap_or_default());
+    }
+
+    let discovery_topic = format!("{}/light/{}/config", config.discovery_prefix, node_id);
+    let discovery = json!({
+        "name": config.name,
+        "unique_id": node_id,
+        "schema": "json",
+        "command_topic": command_topic,
+        "state_topic": state_topic,
+        "availability_topic": availability_topic,
+        "brightness": true,
+        "supported_color_modes": ["brightness"],
+        "effect": true,
+        "effect_list": effects,
+    })
+    .to_string();
+
+    let (client, mut connection) = Client::new(options, 16);
+    println!("MQTT connecting to {}:{}.", config.host, config.port);
+
+    // the connection thread must never wait on the client, since it's the
+    // one that drains the client's queue
+    let (work, jobs) = mpsc::channel();
+    let events_command_topic = command_topic.clone();
+    thread::spawn(move || {
+        for event in connection.iter() {
+            match event {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    let _ = work.send(Job::Announce);
+                }
+                Ok(Event::Incoming(Packet::Publish(publish)))
+                    if publish.topic == events_command_topic =>
+                {
+                    let _ = work.send(Job::Apply(publish.payload.to_vec()));
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    eprintln!("MQTT connection error: {}; retrying.", e);
+                    thread::sleep(Duration::from_secs(5));
+                }
+            }
+        }
+    });
+
+    // applies commands, and publishes state whenever it changes, whoever
+    // changed it
+    thread::spawn(move || {
+        let mut last = String::new();
+        loop {
+            match jobs.recv_timeout(Duration::from_millis(250)) {
+                // (re)announce everything on every connect, since the broker
+                // may have restarted and lost it
+                Ok(Job::Announce) => {
+                    let _ =
+                        client.publish(&discovery_topic, QoS::AtLeastOnce, true, discovery.clone());
+                    let _ = client.publish(&availability_topic, QoS::AtLeastOnce, true, "online");
+                    let _ = client.subscribe(&command_topic, QoS::AtLeastOnce);
+                    last.clear();
+                }
+                Ok(Job::Apply(payload)) => {
+                    if let Err(e) = apply(&payload, &controller) {
+                        eprintln!("MQTT command ignored: {}", e);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            let state = state_json(&controller.status());
+            // while the broker is away the queue fills, and the state is
+            // tried again once there's room
+            if state != last
+                && client
+                    .try_publish(&state_topic, QoS::AtLeastOnce, true, state.clone())
+                    .is_ok()
+            {
+                last = state;
+            }
+        }
+    });
+}
+
+/// Work the connection thread hands off.
+enum Job {
+    /// Connected: send discovery, availability and the subscription.
+    Announce,
+    /// A command arrived on the command topic.
+    Apply(Vec<u8>),
+}
+
+/// Handles a Home Assistant JSON-schema light command, like
+/// `{"state": "ON", "brightness": 128, "effect": "comet"}`.
+fn apply(payload: &[u8], controller: &Controller) -> Result<(), String> {
+    let command: Value = serde_json::from_slice(payload).map_err(|e| e.to_string())?;
+
+    if let Some(effect) = command.get("effect").and_then(Value::as_str) {
+        controller.send(Command::SetEffect(effect.to_string()))?;
+    }
+    if let Some(brightness) = command.get("brightness").and_then(Value::as_u64) {
+        controller.send(Command::SetBrightness(brightness.min(255) as f32 / 255.0))?;
+    }
+    match command.get("state").and_then(Value::as_str) {
+        Some("ON") => controller.send(Command::SetOn(true)),
+        Some("OFF") => controller.send(Command::SetOn(false)),
+        Some(other) => Err(format!("unknown state `{}`", other)),
+        None => Ok(()),
+    }
+}
+
+fn state_json(status: &Status) -> String {
+    json!({
+        "state": if status.on { "ON" } else { "OFF" },
+        "brightness": status.brightness,
+        "effect": status.effect,
+        "color_mode": "brightness",
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        io::{self, Read, Write},
+        net::{TcpListener, TcpStream},
+        time::Instant,
+    };
+
+    use super::*;
+    use crate::control::remote;
+
+    fn read_packet(stream: &mut TcpStream) -> io::Result<(u8, Vec<u8>)> {
+        let mut byte = [0];
+        stream.read_exact(&mut byte)?;
+        let kind = byte[0];
+        let mut len = 0;
+        for shift in (0..28).step_by(7) {
+            stream.read_exact(&mut byte)?;
+            len |= ((byte[0] & 0x7f) as usize) << shift;
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+        }
+        let mut body = vec![0; len];
+        stream.read_exact(&mut body)?;
+        Ok((kind, body))
+    }
+
+    /// Speaks just enough MQTT 3.1.1 to take a connection, acknowledge what
+    /// the client sends, and send a command once it has subscribed. Returns
+    /// the last payload published to each topic once `done` is satisfied.
+    fn broker(
+        listener: TcpListener,
+        command: (&str, &str),
+        done: impl Fn(&HashMap<String, String>) -> bool,
+    ) -> HashMap<String, String> {
+        let (mut stream, _) = listener.accept().unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut published = HashMap::new();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !done(&published) && Instant::now() < deadline {
+            let (kind, body) = read_packet(&mut stream).unwrap();
+            match kind >> 4 {
+                // CONNECT
+                1 => stream.write_all(&[0x20, 2, 0, 0]).unwrap(),
+                // PUBLISH
+                3 => {
+                    let topic_len = u16::from_be_bytes([body[0], body[1]]) as usize;
+                    let topic = String::from_utf8(body[2..2 + topic_len].to_vec()).unwrap();
+                    let mut rest = &body[2 + topic_len..];
+                    if (kind >> 1) & 3 > 0 {
+                        stream.write_all(&[0x40, 2, rest[0], rest[1]]).unwrap();
+                        rest = &rest[2..];
+                    }
+                    published.insert(topic, String::from_utf8(rest.to_vec()).unwrap());
+                }
+                // SUBSCRIBE
+                8 => {
+                    stream.write_all(&[0x90, 3, body[0], body[1], 1]).unwrap();
+                    let (topic, payload) = command;
+                    let mut packet = vec![0x30, (2 + topic.len() + payload.len()) as u8];
+                    packet.extend_from_slice(&(topic.len() as u16).to_be_bytes());
+                    packet.extend_from_slice(topic.as_bytes());
+                    packet.extend_from_slice(payload.as_bytes());
+                    stream.write_all(&packet).unwrap();
+                }
+                // PINGREQ
+                12 => stream.write_all(&[0xd0, 0]).unwrap(),
+                _ => {}
+            }
+        }
+        published
+    }
+
+    #[test]
+    fn announces_itself_and_follows_commands() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let config = MqttConfig {
+            host: "127.0.0.1".into(),
+            port: listener.local_addr().unwrap().port(),
+            username: None,
+            password: None,
+            node_id: "test_strip".into(),
+            name: "Test strip".into(),
+            topic: Some("strip".into()),
+            discovery_prefix: "homeassistant".into(),
+        };
+        let controller = remote::spawn_renderer();
+        connect(&config, controller, vec!["ripples", "comet"]);
+
+        let command = r#"{"state": "OFF", "effect": "comet"}"#;
+        let published = broker(listener, ("strip/set", command), |published| {
+            published
+                .get("strip/state")
+                .is_some_and(|state| state.contains("comet"))
+        });
+
+        let discovery: Value =
+            serde_json::from_str(&published["homeassistant/light/test_strip/config"]).unwrap();
+        assert_eq!(discovery["name"], "Test strip");
+        assert_eq!(discovery["unique_id"], "test_strip");
+        assert_eq!(discovery["command_topic"], "strip/set");
+        assert_eq!(discovery["state_topic"], "strip/state");
+        assert_eq!(discovery["effect_list"], json!(["ripples", "comet"]));
+        assert_eq!(published["strip/availability"], "online");
+
+        let state: Value = serde_json::from_str(&published["strip/state"]).unwrap();
+        assert_eq!(state["effect"], "comet");
+        assert_eq!(state["state"], "OFF");
+    }
+}
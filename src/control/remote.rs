@@ -0,0 +1,179 @@
+use super::{Command, Status};
+use crate::effects::Registry;
+use crate::output::ColorPipeline;
+use crate::playlist::Playlist;
+use crate::stage::Stage;
+
+/// Render loop state that only remote clients change.
+#[derive(Default)]
+pub struct RemoteState {
+    pub paused: bool,
+    /// Blanks the strip, on top of anything the schedule says.
+    pub off: bool,
+}
+
+/// Applies a command from a remote client between frames.
+pub fn handle_command(
+    command: Command,
+    registry: &Registry,
+    stage: &mut Stage,
+    pipeline: &mut ColorPipeline,
+    playlist: &mut Option<Playlist>,
+    remote: &mut RemoteState,
+) -> Result<(), String> {
+    match command {
+        Command::SetEffect(name) => {
+            let index = registry.find(&name)?;
+            // picking an effect by hand takes over from the playlist
+            if playlist.take().is_some() {
+                println!("Playlist stopped.");
+            }
+            stage.switch_to(index);
+            println!("Control: now playing {}.", stage.current_effect().name);
+        }
+        Command::SetParams(params) => {
+            let (effect, driver) = stage.current_driver_mut();
+            // check them all first, so a bad one leaves the effect untouched
+            effect.check_overrides(&params)?;
+            for (name, value) in params {
+                effect.set_param(driver, &name, value)?;
+            }
+        }
+        Command::SetBrightness(brightness) => pipeline.brightness = brightness,
+        Command::SetOn(on) => remote.off = !on,
+        Command::Pause => remote.paused = true,
+        Command::Resume => remote.paused = false,
+    }
+    Ok(())
+}
+
+pub fn status(
+    stage: &Stage,
+    pipeline: &ColorPipeline,
+    remote: &RemoteState,
+    on: bool,
+    fps: Option<f32>,
+    leds: usize,
+) -> Status {
+    let effect = stage.current_effect();
+    let params = effect
+        .params
+        .iter()
+        .filter_map(|p| {
+            let value = effect.get_param(stage.current_driver(), p.name)?;
+            Some((p.name.to_string(), value))
+        })
+        .collect();
+
+    Status {
+        effect: effect.name.to_string(),
+        params,
+        brightness: (pipeline.brightness * 255.0).round() as u8,
+        on,
+        paused: remote.paused,
+        fps,
+        leds,
+    }
+}
+
+/// Runs a render loop over the repo's sample layout on its own thread, for
+/// the servers' tests to talk to. It starts on ripples.
+#[cfg(test)]
+pub fn spawn_renderer() -> super::Controller {
+    use std::{path::Path, thread, time::Duration};
+
+    use crate::layout;
+    use crate::transition::TransitionSettings;
+
+    let (controller, mut control) = super::channel();
+    thread::spawn(move || {
+        let registry = Registry::builtin();
+        let path = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/config.yap"));
+        let sled = layout::load(path).unwrap();
+        let drivers = registry
+            .iter()
+            .map(|effect| (*effect, effect.build(None)))
+            .collect();
+        let first = registry.find("ripples").unwrap();
+        let mut stage = Stage::new(sled, drivers, first, TransitionSettings::default());
+        let mut pipeline = ColorPipeline::default();
+        let mut playlist = None;
+        let mut remote = RemoteState::default();
+        let mut frame = Vec::new();
+        loop {
+            control.handle(|command| {
+                handle_command(
+                    command,
+                    &registry,
+                    &mut stage,
+                    &mut pipeline,
+                    &mut playlist,
+                    &mut remote,
+                )
+            });
+            if !remote.paused {
+                stage.step(None);
+            }
+            stage.render(&mut frame);
+            let status = status(&stage, &pipeline, &remote, !remote.off, None, frame.len());
+            control.publish(status, &frame);
+            thread::sleep(Duration::from_millis(5));
+        }
+    });
+    controller
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::Overrides;
+
+    #[test]
+    fn params_are_set_all_or_nothing() {
+        let controller = spawn_renderer();
+        let params = Overrides::from([("max_ripples".to_string(), 20.0)]);
+        controller.send(Command::SetParams(params)).unwrap();
+        assert_eq!(controller.status().params["max_ripples"], 20.0);
+
+        let params = Overrides::from([("max_ripples".to_string(), 5.0), ("nope".to_string(), 1.0)]);
+        assert!(controller.send(Command::SetParams(params)).is_err());
+        let params = Overrides::from([
+            ("max_ripples".to_string(), 5.0),
+            ("feathering".to_string(), -1.0),
+        ]);
+        assert!(controller.send(Command::SetParams(params)).is_err());
+        assert_eq!(controller.status().params["max_ripples"], 20.0);
+    }
+
+    #[test]
+    fn switching_effect_names_the_choices_on_a_miss() {
+        let controller = spawn_renderer();
+        controller.send(Command::SetEffect("Comet".into())).unwrap();
+        assert_eq!(controller.status().effect, "comet");
+
+        let error = controller
+            .send(Command::SetEffect("nope".into()))
+            .unwrap_err();
+        assert!(error.starts_with("unknown effect `nope`; try one of: "));
+        assert!(error.contains("ripples"));
+        assert_eq!(controller.status().effect, "comet");
+    }
+
+    #[test]
+    fn brightness_power_and_pausing_show_in_the_status() {
+        let controller = spawn_renderer();
+        controller.send(Command::SetBrightness(0.5)).unwrap();
+        controller.send(Command::SetOn(false)).unwrap();
+        controller.send(Command::Pause).unwrap();
+        let status = controller.status();
+        assert_eq!(status.brightness, 128);
+        assert!(!status.on);
+        assert!(status.paused);
+
+        controller.send(Command::SetOn(true)).unwrap();
+        controller.send(Command::Resume).unwrap();
+        let status = controller.status();
+        assert!(status.on);
+        assert!(!status.paused);
+    }
+}
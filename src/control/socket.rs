@@ -0,0 +1,186 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::Path,
+    thread,
+};
+
+use super::{Command, Controller};
+use crate::effects::Overrides;
+
+/// Starts the line protocol `ledctl` speaks on a Unix socket at `path`, on a
+/// background thread. A stale socket left by a previous run is replaced, but
+/// one that another instance is still listening on is not.
+///
+/// Each request is one line, and gets one line back: `ok`, `ok <value>` or
+/// `err <message>`.
+///
+/// - `effects` lists the effect names, separated by spaces.
+/// - `status` returns the same JSON as the HTTP API's `/api/status`.
+/// - `fps` returns the measured frame rate.
+/// - `effect <name>` switches effect.
+/// - `param <name> <value>` sets a parameter of the current effect.
+/// - `brightness <0-255>` sets the global brightness.
+/// - `on`, and `off` or `blackout`, light and blank the strip.
+/// - `pause` and `resume` freeze and unfreeze the effect.
+pub fn serve(
+    path: &Path,
+    controller: Controller,
+    effects: Vec<&'static str>,
+) -> Result<(), String> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(format!("{} is in use by another instance", path.display()));
+        }
+        fs::remove_file(path)
+            .map_err(|e| format!("failed to remove stale {}: {}", path.display(), e))?;
+    }
+    let listener = UnixListener::bind(path)
+        .map_err(|e| format!("failed to listen on {}: {}", path.display(), e))?;
+    println!("Socket control listening on {}.", path.display());
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let controller = controller.clone();
+            let effects = effects.clone();
+            thread::spawn(move || session(stream, &controller, &effects));
+        }
+    });
+    Ok(())
+}
+
+/// Answers requests from one client until it hangs up.
+fn session(stream: UnixStream, controller: &Controller, effects: &[&str]) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match handle(&line, controller, effects) {
+            Ok(None) => "ok".to_string(),
+            Ok(Some(value)) => format!("ok {}", value),
+            Err(e) => format!("err {}", e),
+        };
+        if writeln!(writer, "{}", reply).is_err() {
+            break;
+        }
+    }
+}
+
+fn handle(line: &str, controller: &Controller, effects: &[&str]) -> Result<Option<String>, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let command = match words.as_slice() {
+        ["effects"] => return Ok(Some(effects.join(" "))),
+        ["status"] => {
+            let status = serde_json::to_string(&controller.status()).map_err(|e| e.to_string())?;
+            return Ok(Some(status));
+        }
+        ["fps"] => {
+            let fps = controller
+                .status()
+                .fps
+                .ok_or("no frame rate measured yet")?;
+            return Ok(Some(format!("{:.1}", fps)));
+        }
+        ["effect", name] => Command::SetEffect(name.to_string()),
+        ["param", name, value] => {
+            let value = value
+                .parse()
+                .map_err(|_| format!("`{}` must be a number", name))?;
+            Command::SetParams(Overrides::from([(name.to_string(), value)]))
+        }
+        ["brightness", brightness] => {
+            let brightness: u8 = brightness
+                .parse()
+                .map_err(|_| "brightness must be 0-255".to_string())?;
+            Command::SetBrightness(brightness as f32 / 255.0)
+        }
+        ["on"] => Command::SetOn(true),
+        ["off"] | ["blackout"] => Command::SetOn(false),
+        ["pause"] => Command::Pause,
+        ["resume"] => Command::Resume,
+        _ => return Err(format!("unknown request `{}`", line.trim())),
+    };
+    controller.send(command)?;
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::control::remote;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("socket-{}-{}", std::process::id(), name))
+    }
+
+    /// Sends each request on one connection and collects the replies.
+    fn ask(path: &Path, requests: &[&str]) -> Vec<String> {
+        let mut stream = UnixStream::connect(path).unwrap();
+        let mut replies = BufReader::new(stream.try_clone().unwrap()).lines();
+        requests
+            .iter()
+            .map(|request| {
+                writeln!(stream, "{}", request).unwrap();
+                replies.next().unwrap().unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn answers_requests_line_by_line() {
+        let path = temp_path("requests.sock");
+        let _ = fs::remove_file(&path);
+        serve(&path, remote::spawn_renderer(), vec!["ripples", "comet"]).unwrap();
+
+        let replies = ask(
+            &path,
+            &[
+                "effects",
+                "param max_ripples 20",
+                "param max_ripples lots",
+                "param nope 1",
+                "dance",
+                "fps",
+                "status",
+            ],
+        );
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(replies[0], "ok ripples comet");
+        assert_eq!(replies[1], "ok");
+        assert_eq!(replies[2], "err `max_ripples` must be a number");
+        assert!(replies[3].starts_with("err "));
+        assert_eq!(replies[4], "err unknown request `dance`");
+        // the stand-in render loop never measures its frame rate
+        assert_eq!(replies[5], "err no frame rate measured yet");
+        let status: serde_json::Value =
+            serde_json::from_str(replies[6].strip_prefix("ok ").unwrap()).unwrap();
+        assert_eq!(status["params"]["max_ripples"], 20.0);
+    }
+
+    #[test]
+    fn replaces_a_stale_socket_but_not_a_live_one() {
+        let path = temp_path("stale.sock");
+        let _ = fs::remove_file(&path);
+        // a listener that's gone leaves its socket file behind
+        drop(UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        serve(&path, remote::spawn_renderer(), vec!["ripples"]).unwrap();
+        assert_eq!(ask(&path, &["effects"]), ["ok ripples"]);
+
+        let error = serve(&path, remote::spawn_renderer(), vec!["ripples"]).unwrap_err();
+        assert!(error.ends_with("is in use by another instance"));
+        assert_eq!(ask(&path, &["effects"]), ["ok ripples"]);
+        fs::remove_file(&path).unwrap();
+    }
+}
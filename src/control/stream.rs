@@ -0,0 +1,57 @@
+use std::thread;
+
+use serde_json::json;
+use tiny_http::{Header, Request, Response};
+use tungstenite::{handshake::derive_accept_key, protocol::Role, Message, WebSocket};
+
+use super::Controller;
+
+/// Upgrades `request` to a WebSocket and streams frames to it on a thread of
+/// its own.
+///
+/// The first message, and another whenever the layout changes, is JSON text:
+/// `{"type": "layout", "positions": [[x, y], ...]}`. Every frame after that
+/// is a binary message of RGB bytes, three per LED in index order. A client
+/// that falls behind skips frames rather than queueing them.
+pub fn serve(request: Request, controller: Controller) {
+    let key = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Sec-WebSocket-Key"))
+        .map(|h| h.value.as_str().to_string());
+    let Some(key) = key else {
+        let response = Response::from_string("expected a WebSocket upgrade").with_status_code(400);
+        let _ = request.respond(response);
+        return;
+    };
+
+    let accept = derive_accept_key(key.as_bytes());
+    let response = Response::empty(101)
+        .with_header(Header::from_bytes("Connection", "Upgrade").unwrap())
+        .with_header(Header::from_bytes("Sec-WebSocket-Accept", accept).unwrap());
+    let stream = request.upgrade("websocket", response);
+
+    thread::spawn(move || {
+        let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+        let mut layout_seen = None;
+        let mut frame_seen = 0;
+        loop {
+            let (frame_number, colors) = controller.next_frame(frame_seen);
+            frame_seen = frame_number;
+
+            let (layout_number, positions) = controller.layout();
+            if layout_seen != Some(layout_number) {
+                let layout = json!({ "type": "layout", "positions": positions });
+                if socket.send(Message::text(layout.to_string())).is_err() {
+                    break;
+                }
+                layout_seen = Some(layout_number);
+            }
+
+            // a closed or stalled client shows up as a failed send
+            if socket.send(Message::binary(colors.concat())).is_err() {
+                break;
+            }
+        }
+    });
+}
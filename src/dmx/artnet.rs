@@ -0,0 +1,128 @@
+use std::net::{Ipv4Addr, UdpSocket};
+
+use super::{DmxInput, DmxPacket, DmxSettings};
+
+pub const PORT: u16 = 6454;
+
+const ARTNET_ID: &[u8] = b"Art-Net\0";
+const OP_DMX: u16 = 0x5000;
+const PROTOCOL_VERSION: u16 = 14;
+/// Where the levels start in an ArtDmx packet.
+const HEADER_LEN: usize = 18;
+
+/// Listens for ArtDmx packets, broadcast or unicast, on the standard port.
+///
+/// This node doesn't answer ArtPoll, so consoles that only send to nodes
+/// they've discovered need the Pi's address entered by hand.
+pub fn listen(settings: &DmxSettings, leds: usize) -> Result<DmxInput, String> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, PORT))
+        .map_err(|e| format!("failed to listen for Art-Net on port {}: {}", PORT, e))?;
+
+    // broadcast and unicast need nothing joined
+    let input = DmxInput::spawn("Art-Net", socket, settings, leds, parse, |_, _| {})?;
+    let universes = settings.mapping.universes(leds);
+    println!(
+        "Art-Net listening on port {} for universes {} to {}.",
+        PORT,
+        universes.start(),
+        universes.end()
+    );
+    Ok(input)
+}
+
+/// Builds an ArtDmx packet carrying `levels` for `universe`, a 15-bit port
+/// address. A `sequence` of 0 tells receivers not to reorder.
+pub fn dmx_packet(universe: u16, sequence: u8, levels: &[u8]) -> Vec<u8> {
+    // the length must be even, and at least 2
+    let len = levels.len().max(2).next_multiple_of(2);
+    let mut packet = Vec::with_capacity(HEADER_LEN + len);
+    packet.extend_from_slice(ARTNET_ID);
+    packet.extend_from_slice(&OP_DMX.to_le_bytes());
+    packet.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+    packet.push(sequence);
+    packet.push(0); // physical port
+    packet.extend_from_slice(&universe.to_le_bytes());
+    packet.extend_from_slice(&(len as u16).to_be_bytes());
+    packet.extend_from_slice(levels);
+    packet.resize(HEADER_LEN + len, 0);
+    packet
+}
+
+/// Picks the levels out of an ArtDmx packet. Other opcodes are ignored.
+fn parse(packet: &[u8]) -> Option<DmxPacket<'_>> {
+    if packet.len() < HEADER_LEN
+        || &packet[..8] != ARTNET_ID
+        || u16::from_le_bytes([packet[8], packet[9]]) != OP_DMX
+    {
+        return None;
+    }
+
+    let len = u16::from_be_bytes([packet[16], packet[17]]) as usize;
+    let sequence = packet[12];
+    Some(DmxPacket {
+        universe: u16::from_le_bytes([packet[14], packet[15]]) & 0x7fff,
+        data: &packet[HEADER_LEN..(HEADER_LEN + len).min(packet.len())],
+        sequence: (sequence != 0).then_some(sequence),
+        source: None,
+        terminated: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use sled::color::Srgb;
+
+    use super::*;
+    use crate::output::{ArtNetOutput, LedOutput};
+
+    #[test]
+    fn output_packets_parse_back() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let port = receiver.local_addr().unwrap().port();
+        let mut output = ArtNetOutput::from_spec(&format!("127.0.0.1:{}/3", port)).unwrap();
+
+        let mut frame = vec![Srgb::new(0.0, 0.0, 0.0); 200];
+        frame[0] = Srgb::new(1.0, 0.0, 0.0);
+        frame[169] = Srgb::new(0.0, 1.0, 0.0);
+        frame[170] = Srgb::new(0.0, 0.0, 1.0);
+        output.open(frame.len()).unwrap();
+        for _ in 0..2 {
+            output.write(&frame).unwrap();
+            output.flush().unwrap();
+        }
+
+        let mut buffer = [0; 1500];
+        let mut receive = || {
+            let len = receiver.recv(&mut buffer).unwrap();
+            let packet = parse(&buffer[..len]).unwrap();
+            (packet.universe, packet.sequence, packet.data.to_vec())
+        };
+        let (universe, sequence, data) = receive();
+        assert_eq!((universe, sequence), (3, Some(1)));
+        assert_eq!(data.len(), 510);
+        assert_eq!(data[..3], [255, 0, 0]);
+        assert_eq!(data[507..], [0, 255, 0]);
+
+        let (universe, sequence, data) = receive();
+        assert_eq!((universe, sequence), (4, Some(1)));
+        assert_eq!(data.len(), 90);
+        assert_eq!(data[..3], [0, 0, 255]);
+
+        // the next frame carries the next sequence number
+        assert_eq!(receive().1, Some(2));
+    }
+
+    #[test]
+    fn other_opcodes_are_ignored() {
+        let mut packet = dmx_packet(1, 1, &[1, 2, 3]);
+        assert!(parse(&packet).is_some());
+        packet[9] = 0x20; // OpPoll
+        assert!(parse(&packet).is_none());
+        assert!(parse(b"Art-Net\0").is_none());
+    }
+}
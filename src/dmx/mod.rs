@@ -0,0 +1,316 @@
+use std::{
+    collections::HashMap,
+    net::UdpSocket,
+    ops::RangeInclusive,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use sled::color::Srgb;
+
+pub mod artnet;
+pub mod sacn;
+
+/// DMX channels in a universe.
+const UNIVERSE_SIZE: usize = 512;
+/// An LED never straddles two universes, so a full one holds this many.
+pub const LEDS_PER_UNIVERSE: usize = UNIVERSE_SIZE / 3;
+
+/// Where the strip's colors sit in DMX address space: three channels per LED,
+/// in RGB order, from `channel` of `universe` on. Each following universe
+/// carries on from its own channel 1.
+#[derive(Clone, Copy)]
+pub struct DmxMapping {
+    pub universe: u16,
+    /// 1-512.
+    pub channel: u16,
+}
+
+impl DmxMapping {
+    /// Which LEDs `universe` carries, and where in its data the first one
+    /// starts.
+    fn span(&self, universe: u16) -> Option<(RangeInclusive<usize>, usize)> {
+        let index = universe.checked_sub(self.universe)? as usize;
+        let offset = self.channel as usize - 1;
+        let first_leds = (UNIVERSE_SIZE - offset) / 3;
+        if index == 0 {
+            Some((0..=first_leds.checked_sub(1)?, offset))
+        } else {
+            let start = first_leds + (index - 1) * LEDS_PER_UNIVERSE;
+            Some((start..=start + LEDS_PER_UNIVERSE - 1, 0))
+        }
+    }
+
+    /// The universes needed to carry `leds` LEDs.
+    pub fn universes(&self, leds: usize) -> RangeInclusive<u16> {
+        let first_leds = (UNIVERSE_SIZE - (self.channel as usize - 1)) / 3;
+        let more = leds.saturating_sub(first_leds).div_ceil(LEDS_PER_UNIVERSE);
+        self.universe..=self.universe.saturating_add(more as u16)
+    }
+}
+
+/// How to receive one DMX-over-IP protocol.
+#[derive(Clone, Copy)]
+pub struct DmxSettings {
+    pub mapping: DmxMapping,
+    /// How long to keep showing received data after packets stop, before
+    /// falling back to the effect.
+    pub timeout: Duration,
+}
+
+/// One universe's worth of levels, as a protocol parser found it in a packet.
+struct DmxPacket<'a> {
+    universe: u16,
+    /// Levels from channel 1 on.
+    data: &'a [u8],
+    /// `None` for protocols or senders that don't number their packets.
+    sequence: Option<u8>,
+    /// The sender's own name for itself, if the protocol carries one.
+    source: Option<String>,
+    /// The sender says it's stopping, so there's no need to wait for the
+    /// timeout.
+    terminated: bool,
+}
+
+struct Latest {
+    /// One color per layout LED.
+    colors: Vec<[u8; 3]>,
+    /// When each universe last had data, until it times out or its sender
+    /// says it's done.
+    received: HashMap<u16, Instant>,
+    source: String,
+}
+
+/// Colors arriving over the network, for the render loop to show in place of
+/// the effect while they keep coming. The most recent packet for each
+/// universe wins, whoever sent it. Each universe goes dark on its own when
+/// its packets stop, and the input is live while any of them is.
+pub struct DmxInput {
+    protocol: &'static str,
+    timeout: Duration,
+    mapping: DmxMapping,
+    /// A handle on the receiving socket, for joining more multicast groups.
+    socket: UdpSocket,
+    /// Joins whatever the protocol needs to receive a range of universes.
+    join: fn(&UdpSocket, RangeInclusive<u16>),
+    latest: Arc<Mutex<Latest>>,
+}
+
+impl DmxInput {
+    /// Receives packets for a layout of `leds` LEDs on `socket` on a
+    /// background thread, handing each to `parse`.
+    fn spawn(
+        protocol: &'static str,
+        socket: UdpSocket,
+        settings: &DmxSettings,
+        leds: usize,
+        parse: fn(&[u8]) -> Option<DmxPacket<'_>>,
+        join: fn(&UdpSocket, RangeInclusive<u16>),
+    ) -> Result<Self, String> {
+        let mapping = settings.mapping;
+        join(&socket, mapping.universes(leds));
+        let latest = Arc::new(Mutex::new(Latest {
+            colors: vec![[0; 3]; leds],
+            received: HashMap::new(),
+            source: String::new(),
+        }));
+        let shared = latest.clone();
+        let receiver = socket
+            .try_clone()
+            .map_err(|e| format!("{}: {}", protocol, e))?;
+        thread::spawn(move || {
+            let mut buffer = [0; 1500];
+            let mut sequences = HashMap::new();
+            while let Ok((len, sender)) = receiver.recv_from(&mut buffer) {
+                let Some(packet) = parse(&buffer[..len]) else {
+                    continue;
+                };
+                let Some((leds, offset)) = mapping.span(packet.universe) else {
+                    continue;
+                };
+                if let Some(sequence) = packet.sequence {
+                    // drop packets that arrive late and out of order, as
+                    // E1.31 recommends, but let a restarted sender through
+                    let last = sequences.get(&packet.universe).copied();
+                    let behind = last.map(|last: u8| sequence.wrapping_sub(last) as i8);
+                    if behind.is_some_and(|behind| behind <= 0 && behind > -20) {
+                        continue;
+                    }
+                    sequences.insert(packet.universe, sequence);
+                }
+
+                let mut latest = shared.lock().unwrap();
+                // universes past the end of the layout are someone else's
+                if *leds.start() >= latest.colors.len() {
+                    continue;
+                }
+                if packet.terminated {
+                    latest.received.remove(&packet.universe);
+                    continue;
+                }
+                let levels = packet.data.get(offset..).unwrap_or_default();
+                let colors = &mut latest.colors[*leds.start()..];
+                for (color, rgb) in colors
+                    .iter_mut()
+                    .zip(levels.chunks_exact(3))
+                    .take(leds.count())
+                {
+                    *color = [rgb[0], rgb[1], rgb[2]];
+                }
+                latest.received.insert(packet.universe, Instant::now());
+                latest.source = packet.source.unwrap_or_else(|| sender.ip().to_string());
+            }
+        });
+
+        Ok(DmxInput {
+            protocol,
+            timeout: settings.timeout,
+            mapping,
+            socket,
+            join,
+            latest,
+        })
+    }
+
+    pub fn protocol(&self) -> &'static str {
+        self.protocol
+    }
+
+    /// Who's sending, if any universe had data within the timeout.
+    pub fn source(&self) -> Option<String> {
+        let latest = self.latest.lock().unwrap();
+        let live = latest
+            .received
+            .values()
+            .any(|received| received.elapsed() < self.timeout);
+        live.then(|| latest.source.clone())
+    }
+
+    /// Follows a layout reload to `leds` LEDs, taking on any universes it
+    /// now needs.
+    pub fn resize(&mut self, leds: usize) {
+        (self.join)(&self.socket, self.mapping.universes(leds));
+        self.latest.lock().unwrap().colors.resize(leds, [0; 3]);
+    }
+
+    /// Overwrites `frame` with the received colors. LEDs in universes with no
+    /// data are black.
+    pub fn read(&self, frame: &mut [Srgb]) {
+        frame.fill(Srgb::new(0.0, 0.0, 0.0));
+        let latest = self.latest.lock().unwrap();
+        for (&universe, received) in &latest.received {
+            let Some((leds, _)) = self.mapping.span(universe) else {
+                continue;
+            };
+            if received.elapsed() >= self.timeout {
+                continue;
+            }
+            let colors = frame.iter_mut().zip(&latest.colors);
+            for (color, [r, g, b]) in colors.skip(*leds.start()).take(leds.count()) {
+                *color = Srgb::new(*r as f32 / 255.0, *g as f32 / 255.0, *b as f32 / 255.0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A made-up protocol: universe, then 1 if terminated, then levels.
+    fn parse(packet: &[u8]) -> Option<DmxPacket<'_>> {
+        Some(DmxPacket {
+            universe: packet[0] as u16,
+            data: &packet[2..],
+            sequence: None,
+            source: None,
+            terminated: packet[1] == 1,
+        })
+    }
+
+    /// An input for `leds` LEDs from universe 1, and a socket to send to it.
+    fn input(leds: usize) -> (DmxInput, UdpSocket) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        sender.connect(socket.local_addr().unwrap()).unwrap();
+        let settings = DmxSettings {
+            mapping: DmxMapping {
+                universe: 1,
+                channel: 1,
+            },
+            timeout: Duration::from_secs(5),
+        };
+        let input = DmxInput::spawn("test", socket, &settings, leds, parse, |_, _| {}).unwrap();
+        (input, sender)
+    }
+
+    fn send(sender: &UdpSocket, universe: u8, terminated: bool, levels: &[u8]) {
+        let mut packet = vec![universe, terminated as u8];
+        packet.extend_from_slice(levels);
+        sender.send(&packet).unwrap();
+        // let the receiving thread catch up
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    fn read(input: &DmxInput, leds: usize) -> Vec<[u8; 3]> {
+        let mut frame = vec![Srgb::new(0.5, 0.5, 0.5); leds];
+        input.read(&mut frame);
+        frame.iter().map(crate::output::to_rgb8).collect()
+    }
+
+    #[test]
+    fn universes_past_the_layout_are_ignored() {
+        let (input, sender) = input(200);
+        send(&sender, 9, false, &[255; 510]);
+        assert_eq!(input.source(), None);
+        assert_eq!(input.latest.lock().unwrap().colors.len(), 200);
+
+        send(&sender, 2, false, &[255; 510]);
+        assert_eq!(input.source(), Some("127.0.0.1".into()));
+        let frame = read(&input, 200);
+        assert_eq!(frame[169], [0; 3]);
+        assert_eq!(frame[170], [255; 3]);
+        assert_eq!(frame[199], [255; 3]);
+    }
+
+    #[test]
+    fn a_terminated_universe_leaves_the_others_live() {
+        let (input, sender) = input(200);
+        send(&sender, 1, false, &[10; 510]);
+        send(&sender, 2, false, &[20; 510]);
+        send(&sender, 1, true, &[]);
+
+        assert!(input.source().is_some());
+        let frame = read(&input, 200);
+        assert_eq!(frame[0], [0; 3]);
+        assert_eq!(frame[170], [20; 3]);
+
+        send(&sender, 2, true, &[]);
+        assert_eq!(input.source(), None);
+    }
+
+    #[test]
+    fn resizing_takes_on_more_universes() {
+        let (mut input, sender) = input(100);
+        send(&sender, 2, false, &[30; 510]);
+        assert_eq!(input.source(), None);
+
+        input.resize(300);
+        send(&sender, 2, false, &[30; 510]);
+        assert_eq!(read(&input, 300)[299], [30; 3]);
+    }
+
+    #[test]
+    fn channel_offset_shifts_the_first_universe() {
+        let mapping = DmxMapping {
+            universe: 4,
+            channel: 7,
+        };
+        assert_eq!(mapping.span(3), None);
+        assert_eq!(mapping.span(4), Some((0..=167, 6)));
+        assert_eq!(mapping.span(5), Some((168..=337, 0)));
+        assert_eq!(mapping.universes(168), 4..=4);
+        assert_eq!(mapping.universes(169), 4..=5);
+    }
+}
@@ -0,0 +1,82 @@
+use std::{
+    io,
+    net::{Ipv4Addr, UdpSocket},
+    ops::RangeInclusive,
+};
+
+use super::{DmxInput, DmxPacket, DmxSettings};
+
+const PORT: u16 = 5568;
+
+const ACN_PACKET_ID: &[u8] = b"ASC-E1.17\0\0\0";
+const VECTOR_ROOT_DATA: u32 = 0x04;
+const VECTOR_FRAMING_DATA: u32 = 0x02;
+const VECTOR_DMP_SET_PROPERTY: u8 = 0x02;
+const OPTION_PREVIEW: u8 = 0x80;
+const OPTION_TERMINATED: u8 = 0x40;
+/// Where the DMX start code sits; levels follow it.
+const START_CODE: usize = 125;
+
+/// Listens for E1.31 data on the standard port, both unicast and on the
+/// multicast groups of the universes `leds` LEDs need.
+pub fn listen(settings: &DmxSettings, leds: usize) -> Result<DmxInput, String> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, PORT))
+        .map_err(|e| format!("failed to listen for sACN on port {}: {}", PORT, e))?;
+
+    let input = DmxInput::spawn("sACN", socket, settings, leds, parse, join)?;
+    let universes = settings.mapping.universes(leds);
+    println!(
+        "sACN listening on port {} for universes {} to {}.",
+        PORT,
+        universes.start(),
+        universes.end()
+    );
+    Ok(input)
+}
+
+/// Joins the multicast group of each of `universes` not joined already.
+fn join(socket: &UdpSocket, universes: RangeInclusive<u16>) {
+    for universe in universes {
+        let [high, low] = universe.to_be_bytes();
+        let group = Ipv4Addr::new(239, 255, high, low);
+        match socket.join_multicast_v4(&group, &Ipv4Addr::UNSPECIFIED) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::AddrInUse => {}
+            // unicast still works without it, so this isn't fatal
+            Err(e) => eprintln!("sACN: failed to join {}: {}", group, e),
+        }
+    }
+}
+
+/// Picks the levels out of an E1.31 data packet. Anything else, including
+/// preview data and alternate start codes, is ignored.
+fn parse(packet: &[u8]) -> Option<DmxPacket<'_>> {
+    let u16_at = |i: usize| u16::from_be_bytes([packet[i], packet[i + 1]]);
+    let u32_at =
+        |i: usize| u32::from_be_bytes([packet[i], packet[i + 1], packet[i + 2], packet[i + 3]]);
+
+    if packet.len() <= START_CODE
+        || &packet[4..16] != ACN_PACKET_ID
+        || u32_at(18) != VECTOR_ROOT_DATA
+        || u32_at(40) != VECTOR_FRAMING_DATA
+        || packet[117] != VECTOR_DMP_SET_PROPERTY
+    {
+        return None;
+    }
+    let options = packet[112];
+    if options & OPTION_PREVIEW != 0 || packet[START_CODE] != 0 {
+        return None;
+    }
+
+    // the property count includes the start code
+    let end = (START_CODE + u16_at(123) as usize).min(packet.len());
+    let name = &packet[44..108];
+    let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+    Some(DmxPacket {
+        universe: u16_at(113),
+        data: &packet[START_CODE + 1..end.max(START_CODE + 1)],
+        sequence: Some(packet[111]),
+        source: Some(String::from_utf8_lossy(name).into_owned()).filter(|name| !name.is_empty()),
+        terminated: options & OPTION_TERMINATED != 0,
+    })
+}
@@ -1,8 +1,10 @@
+use sled::driver::{BufferContainer, Driver, TimeInfo};
 use sled::driver_macros::*;
-use sled::driver::{Driver, TimeInfo};
 use sled::SledResult;
 use sled::{color::Rgb, Sled};
 
+use super::{param, Effect, Param};
+
 use std::f32::consts::TAU;
 const INV_TAU: f32 = 1.0 / TAU;
 
@@ -16,7 +18,49 @@ const BLUE: Rgb = Rgb::new(0.4, 0.51, 0.93);
 
 const TRAIL_RADIUS: f32 = 1.2;
 
-#[allow(dead_code)]
+pub const EFFECT: Effect = Effect {
+    name: "comet",
+    description: "Swirling green and blue points swept by a rotating trail.",
+    build_driver,
+    params: &[
+        Param::float(
+            "green_radius",
+            "Time scale of the green swirl. Higher is slower.",
+            GREEN_RADIUS,
+            0.1,
+            20.0,
+        ),
+        Param::integer(
+            "green_count",
+            "Number of green points around the swirl.",
+            GREEN_COUNT,
+            0,
+            512,
+        ),
+        Param::float(
+            "blue_radius",
+            "Time scale of the blue swirl. Higher is slower.",
+            BLUE_RADIUS,
+            0.1,
+            20.0,
+        ),
+        Param::integer(
+            "blue_count",
+            "Number of blue points around the swirl.",
+            BLUE_COUNT,
+            0,
+            512,
+        ),
+        Param::float(
+            "trail_radius",
+            "Time scale of the sweeping trail. Higher is slower.",
+            TRAIL_RADIUS,
+            0.1,
+            20.0,
+        ),
+    ],
+};
+
 pub fn build_driver() -> Driver {
     let mut driver = Driver::new();
     driver.set_draw_commands(draw);
@@ -24,27 +68,29 @@ pub fn build_driver() -> Driver {
 }
 
 #[draw_commands]
-fn draw(sled: &mut Sled, time_info: &TimeInfo) -> SledResult {
+fn draw(sled: &mut Sled, buffers: &BufferContainer, time_info: &TimeInfo) -> SledResult {
     let elapsed = time_info.elapsed.as_secs_f32();
+    let green_count = param(buffers, "green_count")? as usize;
+    let blue_count = param(buffers, "blue_count")? as usize;
 
-    let inner_time_scale = elapsed / GREEN_RADIUS;
-    let outer_time_scale = elapsed / BLUE_RADIUS;
+    let inner_time_scale = elapsed / param(buffers, "green_radius")?;
+    let outer_time_scale = elapsed / param(buffers, "blue_radius")?;
 
     // speckle in swirling green points
-    for i in 0..GREEN_COUNT {
-        let angle = inner_time_scale + (TAU / GREEN_COUNT as f32) * i as f32 % TAU;
+    for i in 0..green_count {
+        let angle = inner_time_scale + (TAU / green_count as f32) * i as f32 % TAU;
         sled.modulate_at_angle(angle, |led| led.color + GREEN);
     }
 
     // speckle in swirling blue points
-    for i in 0..BLUE_COUNT {
-        let angle = outer_time_scale + (TAU / BLUE_COUNT as f32) * i as f32 % TAU;
+    for i in 0..blue_count {
+        let angle = outer_time_scale + (TAU / blue_count as f32) * i as f32 % TAU;
         sled.modulate_at_angle(angle, |led| led.color + BLUE);
     }
 
     // brighten or darken points depending on time and angle to simulate a sweeping
     // trail thing.
-    let radar_time_scale = elapsed / TRAIL_RADIUS;
+    let radar_time_scale = elapsed / param(buffers, "trail_radius")?;
     let angle = (radar_time_scale % TAU) + TAU;
     sled.map(|led| {
         let da = (led.angle() + angle) % TAU;
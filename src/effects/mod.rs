@@ -1,3 +1,127 @@
-pub mod comet;
-pub mod ripples;
-pub mod warpspeed;
\ No newline at end of file
+use sled::driver::Driver;
+
+mod params;
+
+pub use params::{param, Overrides, Param};
+
+/// Everything the rest of the app needs to know about one effect.
+#[derive(Clone, Copy)]
+pub struct Effect {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub build_driver: fn() -> Driver,
+    /// Values the effect reads from its buffers rather than hard-coding, so
+    /// they can be tuned from the config, the command line or the TUI.
+    pub params: &'static [Param],
+}
+
+impl Effect {
+    /// Builds a driver with every parameter initialized, taking values from
+    /// `overrides` where present.
+    pub fn build(&self, overrides: Option<&Overrides>) -> Driver {
+        let mut driver = (self.build_driver)();
+        params::init_params(&mut driver, self.params, overrides);
+        driver
+    }
+
+    pub fn param(&self, name: &str) -> Option<&Param> {
+        self.params.iter().find(|p| p.name == name)
+    }
+
+    /// Checks that every override names one of this effect's parameters and
+    /// fits its type and range.
+    pub fn check_overrides(&self, overrides: &Overrides) -> Result<(), String> {
+        for (name, value) in overrides {
+            self.lookup_param(name)?.check(*value)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a parameter's current value from a driver built for this effect.
+    pub fn get_param(&self, driver: &Driver, name: &str) -> Option<f32> {
+        self.param(name)?;
+        param(driver.buffers(), name).ok()
+    }
+
+    /// Changes a parameter on a driver built for this effect. The effect
+    /// picks up the new value on its next frame.
+    pub fn set_param(&self, driver: &mut Driver, name: &str, value: f32) -> Result<(), String> {
+        let value = self.lookup_param(name)?.check(value)?;
+        driver
+            .buffers_mut()
+            .set_buffer_item(name, 0, value)
+            .map_err(|e| e.to_string())
+    }
+
+    fn lookup_param(&self, name: &str) -> Result<&Param, String> {
+        self.param(name).ok_or_else(|| {
+            let known: Vec<&str> = self.params.iter().map(|p| p.name).collect();
+            format!(
+                "effect `{}` has no parameter `{}`; it has: {}",
+                self.name,
+                name,
+                known.join(", ")
+            )
+        })
+    }
+}
+
+/// Declares each effect module and adds its `EFFECT` to the builtin list, so
+/// a new effect only needs its own file and a name here.
+macro_rules! register_effects {
+    ($($module:ident),* $(,)?) => {
+        $(pub mod $module;)*
+
+        const BUILTIN: &[Effect] = &[$($module::EFFECT),*];
+    };
+}
+
+register_effects!(comet, ripples, warpspeed);
+
+/// The set of effects available to the CLI, TUI and anything else that
+/// switches between them.
+pub struct Registry {
+    effects: Vec<Effect>,
+}
+
+impl Registry {
+    /// A registry of every effect compiled into the binary.
+    pub fn builtin() -> Self {
+        Registry {
+            effects: BUILTIN.to_vec(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Effect> {
+        self.effects
+            .iter()
+            .find(|effect| effect.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The position of the effect called `name` in [`Registry::iter`].
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.effects
+            .iter()
+            .position(|effect| effect.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Like [`Registry::index_of`], but a miss says which names would work.
+    pub fn find(&self, name: &str) -> Result<usize, String> {
+        self.index_of(name).ok_or_else(|| {
+            let names: Vec<&str> = self.names().collect();
+            format!(
+                "unknown effect `{}`; try one of: {}",
+                name,
+                names.join(", ")
+            )
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Effect> {
+        self.effects.iter()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.effects.iter().map(|effect| effect.name)
+    }
+}
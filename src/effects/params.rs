@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use sled::driver::{BufferContainer, Driver};
+use sled::SledError;
+
+/// Parameter values keyed by name, e.g. from `[effects.ripples]` in the app
+/// config.
+pub type Overrides = HashMap<String, f32>;
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamKind {
+    Float,
+    /// Stored as a float like everything else, but only whole numbers are
+    /// accepted.
+    Integer,
+}
+
+/// One tunable value in an effect's schema.
+///
+/// Each parameter lives in a one-element `f32` buffer named after it, so the
+/// effect's commands can read the current value every frame with [`param`]
+/// and anything holding the driver can change it between frames.
+#[derive(Clone, Copy, Serialize)]
+pub struct Param {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub kind: ParamKind,
+    pub default: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Param {
+    pub const fn float(
+        name: &'static str,
+        description: &'static str,
+        default: f32,
+        min: f32,
+        max: f32,
+    ) -> Self {
+        Param {
+            name,
+            description,
+            kind: ParamKind::Float,
+            default,
+            min,
+            max,
+        }
+    }
+
+    pub const fn integer(
+        name: &'static str,
+        description: &'static str,
+        default: usize,
+        min: usize,
+        max: usize,
+    ) -> Self {
+        Param {
+            name,
+            description,
+            kind: ParamKind::Integer,
+            default: default as f32,
+            min: min as f32,
+            max: max as f32,
+        }
+    }
+
+    /// Returns `value` if it fits this parameter's type and range.
+    pub fn check(&self, value: f32) -> Result<f32, String> {
+        if self.kind == ParamKind::Integer && value.fract() != 0.0 {
+            return Err(format!(
+                "`{}` must be a whole number, got {}",
+                self.name, value
+            ));
+        }
+        if !(self.min..=self.max).contains(&value) {
+            return Err(format!(
+                "`{}` must be between {} and {}, got {}",
+                self.name, self.min, self.max, value
+            ));
+        }
+        Ok(value)
+    }
+
+    /// The amount arrow keys and similar controls nudge this parameter by.
+    #[cfg_attr(not(feature = "tui"), allow(dead_code))]
+    pub fn step(&self) -> f32 {
+        match self.kind {
+            ParamKind::Integer => 1.0,
+            ParamKind::Float => (self.max - self.min) / 100.0,
+        }
+    }
+
+    /// Formats `value` the way it would be written in the app config.
+    pub fn format(&self, value: f32) -> String {
+        match self.kind {
+            ParamKind::Integer => format!("{:.0}", value),
+            ParamKind::Float => format!("{:.2}", value),
+        }
+    }
+
+    pub fn kind_name(&self) -> &'static str {
+        match self.kind {
+            ParamKind::Float => "float",
+            ParamKind::Integer => "integer",
+        }
+    }
+}
+
+/// Reads the current value of a parameter inside an effect's commands.
+pub fn param(buffers: &BufferContainer, name: &str) -> Result<f32, SledError> {
+    buffers.get_buffer_item::<f32>(name, 0).copied()
+}
+
+/// Stores every parameter in `schema` into `driver`, using `overrides` where
+/// present and defaults elsewhere.
+pub(super) fn init_params(driver: &mut Driver, schema: &[Param], overrides: Option<&Overrides>) {
+    let buffers = driver.buffers_mut();
+    for p in schema {
+        let value = overrides
+            .and_then(|overrides| overrides.get(p.name))
+            .copied()
+            .unwrap_or(p.default);
+        buffers.create_buffer::<f32>(p.name).push(value);
+    }
+}
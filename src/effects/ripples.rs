@@ -6,32 +6,62 @@ use sled::{driver_macros, SledResult};
 use sled::{color::Rgb, Sled, Vec2};
 use std::ops::Range;
 
+use super::{param, Effect, Param};
+
 const MAX_RIPPLES: usize = 12;
 const MAX_RADIUS: f32 = 12.0;
 const FEATHERING: f32 = 0.15;
-const INV_F: f32 = 1.0 / FEATHERING;
 
-#[allow(dead_code)]
+pub const EFFECT: Effect = Effect {
+    name: "ripples",
+    description: "Colored rings expanding from random points across the layout.",
+    build_driver,
+    params: &[
+        Param::integer(
+            "max_ripples",
+            "How many ripples can exist at once.",
+            MAX_RIPPLES,
+            1,
+            64,
+        ),
+        Param::float(
+            "max_radius",
+            "Radius a ripple grows to before respawning elsewhere.",
+            MAX_RADIUS,
+            1.0,
+            50.0,
+        ),
+        Param::float(
+            "feathering",
+            "Width of a ripple's soft leading edge.",
+            FEATHERING,
+            0.01,
+            2.0,
+        ),
+    ],
+};
+
 pub fn build_driver() -> Driver {
     let mut driver = Driver::new();
 
     driver.set_startup_commands(startup);
     driver.set_compute_commands(compute);
     driver.set_draw_commands(draw);
-    return driver;
+    driver
 }
 
 #[startup_commands]
 fn startup(sled: &mut Sled, buffers: &mut BufferContainer) -> SledResult {
     let sled_bounds = sled.domain();
+    let max_ripples = param(buffers, "max_ripples")? as usize;
 
     let radii = buffers.create_buffer("radii");
-    for _ in 0..MAX_RIPPLES {
+    for _ in 0..max_ripples {
         radii.push(rand_init_radius());
     }
 
     let positions = buffers.create_buffer("positions");
-    for _ in 0..MAX_RIPPLES {
+    for _ in 0..max_ripples {
         positions.push(rand_point_in_range(&sled_bounds));
     }
 
@@ -56,9 +86,20 @@ fn startup(sled: &mut Sled, buffers: &mut BufferContainer) -> SledResult {
 fn compute(sled: &Sled, buffers: &mut BufferContainer, time_info: &TimeInfo) -> SledResult {
     let delta = time_info.delta.as_secs_f32();
     let bounds = sled.domain();
-    for i in 0..MAX_RIPPLES {
+    let max_radius = param(buffers, "max_radius")?;
+    let max_ripples = param(buffers, "max_ripples")? as usize;
+
+    // max_ripples can change while running, so grow or shrink to match
+    buffers
+        .get_buffer_mut::<f32>("radii")?
+        .resize_with(max_ripples, rand_init_radius);
+    buffers
+        .get_buffer_mut::<Vec2>("positions")?
+        .resize_with(max_ripples, || rand_point_in_range(&bounds));
+
+    for i in 0..max_ripples {
         let radius: f32 = *buffers.get_buffer_item("radii", i)?;
-        if radius > MAX_RADIUS {
+        if radius > max_radius {
             let new_pos = rand_point_in_range(&bounds);
             let new_radius = rand_init_radius();
             buffers.set_buffer_item("positions", i, new_pos)?;
@@ -92,12 +133,10 @@ fn draw(sled: &mut Sled, buffers: &BufferContainer) -> SledResult {
     let colors = buffers.get_buffer("colors")?;
     let positions = buffers.get_buffer("positions")?;
     let radii = buffers.get_buffer("radii")?;
-    for i in 0..MAX_RIPPLES {
-        let pos = positions[i];
-        let radius = radii[i];
-
-        if radius > -FEATHERING {
-            draw_ripple_at(sled, pos, radius, colors[i % colors.len()]);
+    let feathering = param(buffers, "feathering")?;
+    for (i, (&pos, &radius)) in positions.iter().zip(radii).enumerate() {
+        if radius > -feathering {
+            draw_ripple_at(sled, pos, radius, feathering, colors[i % colors.len()]);
         }
     }
 
@@ -106,14 +145,15 @@ fn draw(sled: &mut Sled, buffers: &BufferContainer) -> SledResult {
     Ok(())
 }
 
-fn draw_ripple_at(sled: &mut Sled, pos: Vec2, radius: f32, color: Rgb) {
+fn draw_ripple_at(sled: &mut Sled, pos: Vec2, radius: f32, feathering: f32, color: Rgb) {
     let inv_radius = 1.0 / radius;
-    sled.modulate_within_dist_from(radius + FEATHERING, pos, |led| {
+    let inv_f = 1.0 / feathering;
+    sled.modulate_within_dist_from(radius + feathering, pos, |led| {
         let r = led.position().distance(pos);
         if r >= radius {
             let dist = r - radius;
-            if dist < FEATHERING {
-                let factor = (FEATHERING - dist) * INV_F;
+            if dist < feathering {
+                let factor = (feathering - dist) * inv_f;
                 return led.color + color * (factor * inv_radius);
             }
         } else {
@@ -1,14 +1,40 @@
-use sled::driver_macros::*;
 use rand::Rng;
 use sled::driver::{BufferContainer, Driver, TimeInfo};
-use sled::SledResult;
+use sled::driver_macros::*;
 use sled::{color::Rgb, Sled, Vec2};
+use sled::{SledError, SledResult};
+
+use super::{param, Effect, Param};
 
 const NUM_STARS: usize = 5000;
 const VELOCITY: f32 = 6.0;
-const DIRECTION: Vec2 = Vec2::new(-0.7071, -0.7071);
+const DIRECTION: f32 = 225.0;
+/// How far past the center a star flies before it's sent back out.
+const RESPAWN_DISTANCE: f32 = 32.0;
+
+pub const EFFECT: Effect = Effect {
+    name: "warpspeed",
+    description: "Stars streaking past the center point.",
+    build_driver,
+    params: &[
+        Param::integer(
+            "num_stars",
+            "How many stars are in flight.",
+            NUM_STARS,
+            0,
+            20000,
+        ),
+        Param::float("velocity", "How fast stars travel.", VELOCITY, 0.0, 50.0),
+        Param::float(
+            "direction",
+            "Direction stars come from, in degrees counterclockwise from +x.",
+            DIRECTION,
+            0.0,
+            360.0,
+        ),
+    ],
+};
 
-#[allow(dead_code)]
 pub fn build_driver() -> Driver {
     let mut driver = Driver::new();
 
@@ -16,28 +42,19 @@ pub fn build_driver() -> Driver {
     driver.set_compute_commands(compute);
     driver.set_draw_commands(draw);
 
-    return driver;
+    driver
 }
 
 #[startup_commands]
 fn startup(sled: &mut Sled, buffers: &mut BufferContainer) -> SledResult {
+    let num_stars = param(buffers, "num_stars")? as usize;
+    let direction = direction(buffers)?;
     let stars = buffers.create_buffer::<Vec2>("stars");
     let center = sled.center_point();
     let mut rng = rand::thread_rng();
 
-    let orth = DIRECTION.perp();
-
-    for _ in 0..NUM_STARS {
-        let sign = match rng.gen_bool(0.5) {
-            true => 1.0,
-            false => -1.0,
-        };
-
-        let spawn_pos = center
-            + (DIRECTION * rng.gen_range(40.0..300.0))
-            + (orth * rng.gen_range(1.45..35.0) * sign);
-
-        stars.push(spawn_pos);
+    for _ in 0..num_stars {
+        stars.push(spawn_star(center, direction, &mut rng));
     }
 
     let colors = buffers.create_buffer::<Rgb>("colors");
@@ -61,31 +78,43 @@ fn startup(sled: &mut Sled, buffers: &mut BufferContainer) -> SledResult {
 fn compute(sled: &Sled, buffers: &mut BufferContainer, time_info: &TimeInfo) -> SledResult {
     let mut rng = rand::thread_rng();
     let delta = time_info.delta.as_secs_f32();
+    let velocity = param(buffers, "velocity")?;
+    let num_stars = param(buffers, "num_stars")? as usize;
+    let direction = direction(buffers)?;
     let stars = buffers.get_buffer_mut::<Vec2>("stars")?;
     let center = sled.center_point();
 
-    let orth = DIRECTION.perp();
+    // num_stars can change while running, so spawn or drop stars to match
+    stars.resize_with(num_stars, || spawn_star(center, direction, &mut rng));
+    fly(stars, center, direction, velocity * delta, &mut rng);
 
+    Ok(())
+}
+
+/// Moves every star `distance` against `direction`, sending any that have
+/// passed the center back out to the far side.
+fn fly(stars: &mut [Vec2], center: Vec2, direction: Vec2, distance: f32, rng: &mut impl Rng) {
     for star in stars {
-        *star -= DIRECTION * VELOCITY * delta;
-        if star.x.signum() != DIRECTION.x.signum() && star.y.signum() != DIRECTION.y.signum() {
-            let dq = (*star - center).length_squared();
-            if dq > 1000.0 {
-                let sign = match rng.gen_bool(0.5) {
-                    true => 1.0,
-                    false => -1.0,
-                };
-
-                let spawn_pos = center
-                    + (DIRECTION * rng.gen_range(40.0..300.0))
-                    + (orth * rng.gen_range(1.5..35.0) * sign);
-
-                *star = spawn_pos;
-            }
+        *star -= direction * distance;
+        if (*star - center).dot(direction) < -RESPAWN_DISTANCE {
+            *star = spawn_star(center, direction, rng);
         }
     }
+}
 
-    Ok(())
+fn spawn_star(center: Vec2, direction: Vec2, rng: &mut impl Rng) -> Vec2 {
+    let orth = direction.perp();
+    let sign = match rng.gen_bool(0.5) {
+        true => 1.0,
+        false => -1.0,
+    };
+
+    center + (direction * rng.gen_range(40.0..300.0)) + (orth * rng.gen_range(1.5..35.0) * sign)
+}
+
+fn direction(buffers: &BufferContainer) -> Result<Vec2, SledError> {
+    let angle = param(buffers, "direction")?.to_radians();
+    Ok(Vec2::new(angle.cos(), angle.sin()))
 }
 
 #[draw_commands]
@@ -98,16 +127,47 @@ fn draw(sled: &mut Sled, buffers: &BufferContainer, time_info: &TimeInfo) -> Sle
 
     sled.for_each(|led| led.color *= fade_amount);
 
-    let mut i = 0;
-    for star in stars {
+    for (i, star) in stars.iter().enumerate() {
         let d = Vec2::new(star.x - center.x, star.y - center.y);
         let c = *buffers.get_buffer_item::<Rgb>("colors", i % 10)?;
         sled.modulate_at_dir(d, |led| {
             let d_sq = (d.length() - led.distance()).powi(2);
             led.color + (c / d_sq)
         });
-        i += 1;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stars_keep_coming_from_every_direction() {
+        let mut rng = rand::thread_rng();
+        let center = Vec2::new(3.0, -2.0);
+        for degrees in [0.0_f32, 45.0, 90.0, 180.0, 225.0, 270.0, 300.0] {
+            let angle = degrees.to_radians();
+            let direction = Vec2::new(angle.cos(), angle.sin());
+            let mut stars: Vec<Vec2> = (0..200)
+                .map(|_| spawn_star(center, direction, &mut rng))
+                .collect();
+
+            // long enough for every star to cross the center a few times
+            for _ in 0..2000 {
+                fly(&mut stars, center, direction, VELOCITY / 10.0, &mut rng);
+            }
+            for star in &stars {
+                let along = (*star - center).dot(direction);
+                assert!(
+                    (-RESPAWN_DISTANCE..=300.0).contains(&along),
+                    "a star flying at {} degrees got stuck {} along",
+                    degrees,
+                    along
+                );
+                assert!((*star - center).length() < 310.0);
+            }
+        }
+    }
+}
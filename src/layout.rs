@@ -0,0 +1,81 @@
+use std::{
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use sled::Sled;
+
+/// Parses a sled layout file (.toml or .yap).
+pub fn load(path: &Path) -> Result<Sled, String> {
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| format!("{} is not a valid UTF-8 path", path.display()))?;
+    Sled::new(path_str).map_err(|e| format!("failed to load {}: {}", path.display(), e))
+}
+
+/// The range of LED indices each line segment of `sled` covers, in segment
+/// order.
+pub fn segments(sled: &Sled) -> Vec<Range<usize>> {
+    let mut segments: Vec<Range<usize>> = Vec::new();
+    // `for_each` is the only way to visit every LED, and it wants a mutable
+    // sled
+    let mut sled = sled.clone();
+    sled.for_each(|led| {
+        let (segment, index) = (led.segment() as usize, led.index() as usize);
+        if segments.len() <= segment {
+            segments.resize(segment + 1, index..index);
+        }
+        let range = &mut segments[segment];
+        range.start = range.start.min(index);
+        range.end = range.end.max(index + 1);
+    });
+    segments
+}
+
+/// Polls a layout file and reparses it whenever it changes, so segments can be
+/// adjusted while mounting LEDs without restarting the process.
+pub struct LayoutWatcher {
+    path: PathBuf,
+    interval: Duration,
+    last_check: Instant,
+    last_modified: Option<SystemTime>,
+}
+
+impl LayoutWatcher {
+    pub fn new(path: PathBuf, interval: Duration) -> Self {
+        let last_modified = modified(&path);
+        LayoutWatcher {
+            path,
+            interval,
+            last_check: Instant::now(),
+            last_modified,
+        }
+    }
+
+    /// Returns the newly parsed layout if the file changed since the last
+    /// poll. Cheap to call every frame; the file is only checked once per
+    /// interval.
+    ///
+    /// A file that fails to parse is reported once and then ignored until
+    /// it changes again.
+    pub fn poll(&mut self) -> Option<Result<Sled, String>> {
+        if self.last_check.elapsed() < self.interval {
+            return None;
+        }
+        self.last_check = Instant::now();
+
+        let modified = modified(&self.path);
+        if modified.is_none() || modified == self.last_modified {
+            return None;
+        }
+        self.last_modified = modified;
+
+        Some(load(&self.path))
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -1,100 +1,470 @@
-use core::num;
-use std::time::Instant;
+use std::{fs, io, ops::Range, path::Path, process, time::Duration, time::Instant};
 
-use rs_ws281x::{ChannelBuilder, Controller, ControllerBuilder};
+use clap::Parser;
 use sled::{color::Srgb, Sled};
 
+mod cli;
+mod config;
+mod control;
+mod dmx;
 mod effects;
-// mod tui;
-use effects::*;
-
-// use crossterm::{
-//     terminal::{disable_raw_mode, LeaveAlternateScreen},
-//     ExecutableCommand,
-// };
-
-// use std::{
-//     collections::HashMap,
-//     io::{stdout, Result},
-// };
-
-// fn main() -> Result<()> {
-//     let sled = Sled::new("./config.toml").unwrap();
-//     let mut drivers = HashMap::new();
-//     drivers.insert(tui::Effect::Comet, comet::build_driver());
-//     drivers.insert(tui::Effect::Ripples, ripples::build_driver());
-//     drivers.insert(tui::Effect::Warpspeed, warpspeed::build_driver());
-
-//     let mut app = tui::App::new(sled, drivers);
-//     let mut gpio_controller = construct_gpio_controller(400);
-
-//     while !app.should_quit() {
-//         app.heartbeat()?;
-//         if !app.should_pause() {
-//             let colors = app.drivers.get(&app.current_effect).unwrap().colors_coerced::<u8>();
-//             update_gpio(&mut gpio_controller, colors);
-//         }
-//     }
-
-//     stdout().execute(LeaveAlternateScreen)?;
-//     disable_raw_mode()?;
-//     Ok(())
-// }
+mod layout;
+mod output;
+mod playlist;
+mod schedule;
+mod shutdown;
+mod stage;
+mod timing;
+mod transition;
+#[cfg(feature = "tui")]
+mod tui;
+
+use cli::{Cli, Command, RunArgs};
+use config::Settings;
+use control::remote::{handle_command, status, RemoteState};
+use effects::Registry;
+use layout::LayoutWatcher;
+use output::{ColorPipeline, LedOutput, MappedOutput};
+use schedule::ScheduleState;
+use shutdown::ShutdownSignal;
+use stage::Stage;
+use timing::{FramePacer, FrameStats};
 
 fn main() {
-    let sled = Sled::new("./config.yap").unwrap();
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Run(args) => run(args),
+        Command::Preview(args) => preview(args),
+        Command::ListEffects => {
+            for effect in Registry::builtin().iter() {
+                println!("{:<12} {}", effect.name, effect.description);
+                for p in effect.params {
+                    println!(
+                        "    {:<14} {:<8} {} (default {}, range {}..={})",
+                        p.name,
+                        p.kind_name(),
+                        p.description,
+                        p.default,
+                        p.min,
+                        p.max
+                    );
+                }
+            }
+            Ok(())
+        }
+        Command::ValidateLayout { config } => validate_layout(&config),
+    };
+
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(args: RunArgs) -> Result<(), String> {
+    let registry = Registry::builtin();
+    let settings = Settings::resolve(&args, &registry)?;
+    let mut playlist = settings.playlist.clone();
+    let default_effect = match &playlist {
+        Some(playlist) => playlist.current().effect,
+        None => registry.find(&settings.effect)?,
+    };
+
+    let mut pipeline = settings.pipeline.clone();
+    let mut scheduled = ScheduleState::default();
+    if let Some(schedule) = &settings.schedule {
+        let state = schedule.state();
+        println!(
+            "Schedule: starting at {}.",
+            schedule.now().format("%a %H:%M")
+        );
+        apply_schedule(&state, &scheduled, &mut pipeline, &settings);
+        scheduled = state;
+    }
+    let first_effect = scheduled.effect.unwrap_or(default_effect);
+
+    let sled = layout::load(&settings.layout)?;
     let num_leds = sled.num_leds();
+    let segments = layout::segments(&sled);
     println!("Starting SLED system of {} LEDs.", num_leds);
 
-    let mut driver = ripples::build_driver();
-    driver.mount(sled);
-
-    let mut gpio_controller = construct_gpio_controller(num_leds);
-    let mut last_printout = Instant::now();
-    let mut updates = 0;
-    loop {
-        updates += 1;
-        if last_printout.elapsed().as_secs_f32() > 2.0 {
-            let hz = (updates as f32) / 2.0;
-            println!("Running at {} Hz.", hz);
-            updates = 0;
-            last_printout = Instant::now();
+    let mut stage = build_stage(&registry, &settings, sled, first_effect);
+    let effect = stage.current_effect();
+    let params: Vec<String> = effect
+        .params
+        .iter()
+        .filter_map(|p| {
+            Some(format!(
+                "{}={}",
+                p.name,
+                p.format(effect.get_param(stage.current_driver(), p.name)?)
+            ))
+        })
+        .collect();
+    println!("Running {} ({}).", effect.name, params.join(", "));
+
+    let shutdown = ShutdownSignal::install()?;
+    let (mut output, mut output_leds) = open_output(&settings, num_leds, &segments)?;
+    let (controller, mut control) = control::channel();
+    control.publish_layout(stage.current_driver().positions());
+    if let Some(addr) = settings.http {
+        let effects = registry.iter().copied().collect();
+        control::http::serve(addr, controller.clone(), effects)?;
+    }
+    if let Some(path) = &settings.socket {
+        control::socket::serve(path, controller.clone(), registry.names().collect())?;
+    }
+    #[cfg(feature = "mqtt")]
+    if let Some(mqtt) = &settings.mqtt {
+        control::mqtt::connect(mqtt, controller.clone(), registry.names().collect());
+    }
+    let mut dmx = Vec::new();
+    if let Some(sacn) = &settings.sacn {
+        dmx.push(dmx::sacn::listen(sacn, num_leds)?);
+    }
+    if let Some(artnet) = &settings.artnet {
+        dmx.push(dmx::artnet::listen(artnet, num_leds)?);
+    }
+    // the input being shown, by index, and who's sending to it
+    let mut dmx_live: Option<(usize, String)> = None;
+    let mut remote = RemoteState::default();
+    let mut fps = None;
+
+    let mut watcher = layout_watcher(&settings);
+    let limiter = &settings.limiter;
+    let mut pacer = FramePacer::new(settings.fps);
+    let mut stats = FrameStats::new(Duration::from_secs(2));
+
+    let mut frame: Vec<Srgb> = Vec::with_capacity(num_leds);
+    // the frame as the effect drew it, for clients to preview
+    let mut drawn: Vec<Srgb> = Vec::with_capacity(num_leds);
+    while !shutdown.requested() {
+        let frame_start = Instant::now();
+        if let Some(watcher) = &mut watcher {
+            if let Err(e) = poll_layout(
+                watcher,
+                |sled| {
+                    for input in &mut dmx {
+                        input.resize(sled.num_leds());
+                    }
+                    stage.remount(sled);
+                    control.publish_layout(stage.current_driver().positions());
+                },
+                &mut output,
+                &mut output_leds,
+                &settings,
+            ) {
+                eprintln!("Failed to reopen output: {}", e);
+                break;
+            }
+        }
+
+        control.handle(|command| {
+            handle_command(
+                command,
+                &registry,
+                &mut stage,
+                &mut pipeline,
+                &mut playlist,
+                &mut remote,
+            )
+        });
+
+        if let Some(schedule) = &settings.schedule {
+            let state = schedule.state();
+            if state != scheduled {
+                if state.effect != scheduled.effect {
+                    let fallback = playlist
+                        .as_ref()
+                        .map_or(default_effect, |p| p.current().effect);
+                    stage.switch_to(state.effect.unwrap_or(fallback));
+                    println!("Schedule: now playing {}.", stage.current_effect().name);
+                }
+                apply_schedule(&state, &scheduled, &mut pipeline, &settings);
+                scheduled = state;
+            }
+        }
+
+        // the first input with anything to show wins
+        let live = dmx
+            .iter()
+            .enumerate()
+            .find_map(|(i, input)| Some((i, input.source()?)));
+        if live != dmx_live {
+            match (&live, &dmx_live) {
+                (Some((i, source)), _) => {
+                    println!("{}: receiving from {}.", dmx[*i].protocol(), source)
+                }
+                (None, Some((i, _))) => println!(
+                    "{}: no data, back to {}.",
+                    dmx[*i].protocol(),
+                    stage.current_effect().name
+                ),
+                (None, None) => {}
+            }
+            dmx_live = live;
+        }
+
+        let off = scheduled.off || remote.off;
+        // the effect holds still while it's hidden behind network input
+        if !off && !remote.paused && dmx_live.is_none() {
+            let delta = stage.step(pacer.period().filter(|_| settings.fixed_step));
+            // a scheduled effect holds the playlist where it is
+            let playlist = playlist.as_mut().filter(|_| scheduled.effect.is_none());
+            if let Some(entry) = playlist.and_then(|p| p.advance(delta)) {
+                stage.switch_with(
+                    entry.effect,
+                    entry.transition.unwrap_or(settings.transition),
+                );
+                println!("Playlist: now playing {}.", stage.current_effect().name);
+            }
+        }
+        stage.render(&mut frame);
+        if let Some((i, _)) = &dmx_live {
+            dmx[*i].read(&mut frame);
+        }
+        if off {
+            frame.fill(Srgb::new(0.0, 0.0, 0.0));
+        }
+        drawn.clone_from(&frame);
+        pipeline.apply(&mut frame);
+        let power = limiter.limit(&mut frame);
+        if let Err(e) = output.write(&frame).and_then(|_| output.flush()) {
+            eprintln!("Failed to write frame: {}", e);
+            break;
+        }
+        let status = status(&stage, &pipeline, &remote, !off, fps, frame.len());
+        control.publish(status, &drawn);
+
+        stats.record(frame_start.elapsed());
+        stats.record_power(&power);
+        if pacer.wait() {
+            stats.record_dropped();
+        }
+        if let Some(summary) = stats.take_summary() {
+            fps = Some(summary.hz);
+            println!("{}", summary);
         }
-        driver.step();
-        let colors = driver.colors();
-        update_gpio(&mut gpio_controller, colors);
     }
+
+    println!("Shutting down.");
+    if let Some(path) = &settings.socket {
+        let _ = fs::remove_file(path);
+    }
+    output::blackout_and_close(output.as_mut(), output_leds).map_err(|e| e.to_string())
 }
 
-fn construct_gpio_controller(num_leds: usize) -> Controller {
-    ControllerBuilder::new()
-        
-        .channel(
-            0,
-            ChannelBuilder::new()
-                .pin(18)
-                .count(num_leds as i32)
-                .strip_type(rs_ws281x::StripType::Ws2811Gbr)
-                .brightness(255)
-                .build(),
-        )
-        .build()
-        .unwrap()
+#[cfg(feature = "tui")]
+fn preview(args: RunArgs) -> Result<(), String> {
+    use crossterm::{
+        terminal::{disable_raw_mode, LeaveAlternateScreen},
+        ExecutableCommand,
+    };
+    use std::io::stdout;
+
+    let registry = Registry::builtin();
+    let settings = Settings::resolve(&args, &registry)?;
+    let first_effect = registry.find(&settings.effect)?;
+
+    let sled = layout::load(&settings.layout)?;
+    let num_leds = sled.num_leds();
+    let segments = layout::segments(&sled);
+
+    let shutdown = ShutdownSignal::install()?;
+    let (mut output, mut output_leds) = open_output(&settings, num_leds, &segments)?;
+    let mut app = tui::App::new(build_stage(&registry, &settings, sled, first_effect));
+    let result = preview_loop(
+        &mut app,
+        &mut output,
+        &mut output_leds,
+        &settings,
+        &shutdown,
+    );
+
+    let _ = stdout().execute(LeaveAlternateScreen);
+    let _ = disable_raw_mode();
+    // blank the strip even if the loop failed, reporting the loop's error first
+    let closed = output::blackout_and_close(output.as_mut(), output_leds);
+    result.and(closed).map_err(|e| e.to_string())
 }
 
-fn update_gpio<'a>(controller: &mut Controller, colors: impl Iterator<Item = &'a Srgb>) {
-    let leds = controller.leds_mut(0);
-
-    let mut i = 0;
-    for color in colors {
-        leds[i] = [
-            (color.red * 255.0) as u8,
-            (color.green * 255.0) as u8,
-            (color.blue * 255.0) as u8,
-            0,
-        ];
-        i += 1;
+#[cfg(feature = "tui")]
+fn preview_loop(
+    app: &mut tui::App,
+    output: &mut Box<dyn LedOutput>,
+    output_leds: &mut usize,
+    settings: &Settings,
+    shutdown: &ShutdownSignal,
+) -> io::Result<()> {
+    let mut watcher = layout_watcher(settings);
+    let mut pacer = FramePacer::new(settings.fps);
+    let mut frame: Vec<Srgb> = Vec::new();
+    while !app.should_quit() && !shutdown.requested() {
+        if let Some(watcher) = &mut watcher {
+            poll_layout(
+                watcher,
+                |sled| app.remount(sled),
+                output,
+                output_leds,
+                settings,
+            )?;
+        }
+
+        app.heartbeat(pacer.period().filter(|_| settings.fixed_step))?;
+        if !app.should_pause() {
+            app.stage().render(&mut frame);
+            settings.pipeline.apply(&mut frame);
+            settings.limiter.limit(&mut frame);
+            output.write(&frame)?;
+            output.flush()?;
+        }
+        pacer.wait();
     }
+    Ok(())
+}
+
+#[cfg(not(feature = "tui"))]
+fn preview(_args: RunArgs) -> Result<(), String> {
+    Err("this build has no terminal preview; rebuild with `--features tui`".into())
+}
+
+/// Applies the brightness and on/off parts of a change in what the schedule
+/// asks for.
+fn apply_schedule(
+    state: &ScheduleState,
+    previous: &ScheduleState,
+    pipeline: &mut ColorPipeline,
+    settings: &Settings,
+) {
+    if state.brightness != previous.brightness {
+        pipeline.brightness = state.brightness.unwrap_or(settings.pipeline.brightness);
+        println!("Schedule: brightness {:.0}%.", pipeline.brightness * 100.0);
+    }
+    if state.off != previous.off {
+        println!("Schedule: strip {}.", if state.off { "off" } else { "on" });
+    }
+}
+
+fn validate_layout(path: &Path) -> Result<(), String> {
+    let sled = layout::load(path)?;
+    let domain = sled.domain();
+    let center = sled.center_point();
+    println!("{} is valid.", path.display());
+    println!("  LEDs:   {}", sled.num_leds());
+    println!(
+        "  domain: ({}, {}) to ({}, {})",
+        domain.start.x, domain.start.y, domain.end.x, domain.end.y
+    );
+    println!("  center: ({}, {})", center.x, center.y);
+    Ok(())
+}
+
+fn layout_watcher(settings: &Settings) -> Option<LayoutWatcher> {
+    settings
+        .watch_layout
+        .then(|| LayoutWatcher::new(settings.layout.clone(), Duration::from_secs(1)))
+}
 
-    controller.render().unwrap();
+/// Hands a changed layout to `remount`, keeping the old one if the new file
+/// doesn't parse. The output is reopened if the LED count changed and isn't
+/// pinned by the `leds` setting, and an output map is always built again.
+fn poll_layout(
+    watcher: &mut LayoutWatcher,
+    remount: impl FnOnce(Sled),
+    output: &mut Box<dyn LedOutput>,
+    output_leds: &mut usize,
+    settings: &Settings,
+) -> io::Result<()> {
+    let sled = match watcher.poll() {
+        Some(Ok(sled)) => sled,
+        Some(Err(e)) => {
+            eprintln!("Keeping the current layout: {}", e);
+            return Ok(());
+        }
+        None => return Ok(()),
+    };
+
+    // a map depends on where the segments fall, so a layout it no longer
+    // fits is turned away like one that doesn't parse
+    let rebuilt = if settings.map.is_empty() {
+        None
+    } else {
+        match build_output(settings, sled.num_leds(), &layout::segments(&sled), true) {
+            Ok(rebuilt) => Some(rebuilt),
+            Err(e) => {
+                eprintln!("Keeping the current layout: {}", e);
+                return Ok(());
+            }
+        }
+    };
+
+    let num_leds = sled.num_leds();
+    remount(sled);
+    println!("Reloaded layout with {} LEDs.", num_leds);
+
+    if let Some((mut rebuilt, leds)) = rebuilt {
+        output.close()?;
+        rebuilt.open(leds)?;
+        *output = rebuilt;
+        *output_leds = leds;
+    } else if settings.leds.is_none() && num_leds != *output_leds {
+        output.close()?;
+        output.open(num_leds)?;
+        *output_leds = num_leds;
+    }
+    Ok(())
+}
+
+/// Builds a driver for every effect in `registry` with its configured
+/// parameters, and starts the one at `first` on `sled`.
+fn build_stage(registry: &Registry, settings: &Settings, sled: Sled, first: usize) -> Stage {
+    let drivers = registry
+        .iter()
+        .map(|effect| (*effect, effect.build(settings.overrides_for(effect))))
+        .collect();
+    Stage::new(sled, drivers, first, settings.transition)
+}
+
+/// Builds the requested output for a layout of `num_leds` LEDs, whose line
+/// segments cover `segments`, without opening it. Returns it along with the
+/// number of physical LEDs to open it for. `reload` makes recordings add to
+/// the files the current output has been writing.
+fn build_output(
+    settings: &Settings,
+    num_leds: usize,
+    segments: &[Range<usize>],
+    reload: bool,
+) -> Result<(Box<dyn LedOutput>, usize), String> {
+    if settings.map.is_empty() {
+        let output = output::from_spec(&settings.output, &settings.gpio, &settings.ddp, reload)?;
+        return Ok((output, settings.leds.unwrap_or(num_leds)));
+    }
+
+    let output = MappedOutput::new(
+        &settings.map,
+        segments,
+        num_leds,
+        &settings.gpio,
+        &settings.ddp,
+        reload,
+    )?;
+    Ok((Box::new(output), num_leds))
+}
+
+/// Opens the requested output, returning it along with the number of physical
+/// LEDs it was opened for.
+fn open_output(
+    settings: &Settings,
+    num_leds: usize,
+    segments: &[Range<usize>],
+) -> Result<(Box<dyn LedOutput>, usize), String> {
+    let (mut output, output_leds) = build_output(settings, num_leds, segments, false)?;
+    output.open(output_leds).map_err(|e| {
+        if settings.map.is_empty() {
+            format!("failed to open output `{}`: {}", settings.output, e)
+        } else {
+            format!("failed to open the mapped outputs: {}", e)
+        }
+    })?;
+    Ok((output, output_leds))
 }
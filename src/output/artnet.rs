@@ -0,0 +1,130 @@
+use std::{
+    io,
+    net::{Ipv4Addr, UdpSocket},
+};
+
+use sled::color::Srgb;
+
+use super::{to_rgb8, LedOutput};
+use crate::dmx::{self, artnet};
+
+/// Art-Net port addresses are 15 bits.
+const MAX_UNIVERSE: u16 = 0x7fff;
+
+/// Sends frames to an Art-Net node as ArtDmx packets: three channels per
+/// LED, 170 LEDs per universe, counting up from the first universe. The
+/// universes can't run past the last 15-bit port address.
+pub struct ArtNetOutput {
+    /// `host:port`, which may be a broadcast address.
+    target: String,
+    universe: u16,
+    socket: Option<UdpSocket>,
+    staged: Vec<[u8; 3]>,
+    /// 1-255, then back to 1; 0 would tell the node not to reorder.
+    sequence: u8,
+}
+
+impl ArtNetOutput {
+    /// Parses the part of an output spec after `artnet:`, i.e.
+    /// `<host>[:<port>][/<universe>]`, e.g. `192.168.1.50/3`.
+    pub fn from_spec(arg: &str) -> Result<Self, String> {
+        let (target, universe) = match arg.split_once('/') {
+            Some((target, universe)) => {
+                let universe = universe
+                    .parse()
+                    .ok()
+                    .filter(|u| *u <= MAX_UNIVERSE)
+                    .ok_or_else(|| format!("invalid Art-Net universe `{}`", universe))?;
+                (target, universe)
+            }
+            None => (arg, 0),
+        };
+        if target.is_empty() {
+            return Err("the artnet output needs a host, e.g. `artnet:192.168.1.50`".into());
+        }
+        let target = if target.contains(':') {
+            target.to_string()
+        } else {
+            format!("{}:{}", target, artnet::PORT)
+        };
+
+        Ok(ArtNetOutput {
+            target,
+            universe,
+            socket: None,
+            staged: Vec::new(),
+            sequence: 1,
+        })
+    }
+
+    fn socket(&self) -> io::Result<&UdpSocket> {
+        self.socket
+            .as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "Art-Net output not open"))
+    }
+}
+
+impl LedOutput for ArtNetOutput {
+    fn open(&mut self, num_leds: usize) -> io::Result<()> {
+        let universes = num_leds.div_ceil(dmx::LEDS_PER_UNIVERSE).max(1);
+        let last = self.universe as usize + universes - 1;
+        if last > MAX_UNIVERSE as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} LEDs from Art-Net universe {} need universes up to {}, past {}",
+                    num_leds, self.universe, last, MAX_UNIVERSE
+                ),
+            ));
+        }
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        socket.set_broadcast(true)?;
+        socket.connect(&self.target)?;
+        println!(
+            "Art-Net output sending {} LEDs to {} from universe {}.",
+            num_leds, self.target, self.universe
+        );
+        self.socket = Some(socket);
+        self.staged = vec![[0; 3]; num_leds];
+        Ok(())
+    }
+
+    fn write(&mut self, frame: &[Srgb]) -> io::Result<()> {
+        for (staged, color) in self.staged.iter_mut().zip(frame) {
+            *staged = to_rgb8(color);
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let socket = self.socket()?;
+        for (i, leds) in self.staged.chunks(dmx::LEDS_PER_UNIVERSE).enumerate() {
+            let universe = self.universe + i as u16;
+            socket.send(&artnet::dmx_packet(
+                universe,
+                self.sequence,
+                leds.as_flattened(),
+            ))?;
+        }
+        self.sequence = self.sequence.checked_add(1).unwrap_or(1);
+        Ok(())
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        self.socket = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn universes_cannot_run_past_the_last_port_address() {
+        let mut output = ArtNetOutput::from_spec("127.0.0.1/32766").unwrap();
+        assert!(output.open(340).is_ok());
+        assert!(output.open(341).is_err());
+        assert!(ArtNetOutput::from_spec("127.0.0.1/32768").is_err());
+    }
+}
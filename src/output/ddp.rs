@@ -0,0 +1,285 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket},
+};
+
+use sled::color::Srgb;
+
+use super::{to_rgb8, LedOutput};
+
+const PORT: u16 = 4048;
+
+const HEADER_LEN: usize = 10;
+const FLAG_VERSION_1: u8 = 0x40;
+/// Set on a frame's last packet, telling the controller to show it.
+const FLAG_PUSH: u8 = 0x01;
+/// 8-bit RGB.
+const TYPE_RGB24: u8 = 0x0b;
+/// The controller's default output.
+const DESTINATION_DISPLAY: u8 = 0x01;
+
+/// Which LEDs go to one DDP controller, such as a WLED board.
+#[derive(Clone)]
+pub struct DdpController {
+    pub host: String,
+    pub port: Option<u16>,
+    /// Pixel the data lands on in the controller's own buffer.
+    pub offset: usize,
+    /// How many of the sled's LEDs it takes, in index order after the
+    /// previous controller's. `None` takes the rest.
+    pub leds: Option<usize>,
+}
+
+/// Settings used when building a `ddp` output.
+#[derive(Clone)]
+pub struct DdpSettings {
+    /// Pixels per packet. 480 keeps packets under a standard MTU.
+    pub chunk: usize,
+    /// Used by a bare `ddp` output spec.
+    pub controllers: Vec<DdpController>,
+}
+
+impl DdpSettings {
+    /// The most pixels a single UDP datagram can carry.
+    pub const MAX_CHUNK: usize = (65_507 - HEADER_LEN) / 3;
+}
+
+impl Default for DdpSettings {
+    fn default() -> Self {
+        DdpSettings {
+            chunk: 480,
+            controllers: Vec::new(),
+        }
+    }
+}
+
+/// Sends frames to one or more networked controllers over the Distributed
+/// Display Protocol, splitting the frame between them in order.
+pub struct DdpOutput {
+    controllers: Vec<DdpController>,
+    chunk: usize,
+    /// Each controller's address, a socket of the same family to send from,
+    /// and the range of the frame it takes. Empty while closed.
+    routes: Vec<(SocketAddr, UdpSocket, usize, usize)>,
+    staged: Vec<[u8; 3]>,
+    /// 1-15, then back to 1; 0 would tell controllers not to reorder.
+    sequence: u8,
+}
+
+impl DdpOutput {
+    /// An output to the configured controllers for `ddp`, or to the one named
+    /// by the part of the spec after `ddp:`, i.e. `<host>[:<port>]`. An IPv6
+    /// address needs brackets to take a port, as in `[fe80::1]:4048`.
+    pub fn from_spec(arg: Option<&str>, settings: &DdpSettings) -> Result<Self, String> {
+        let controllers = match arg {
+            Some("") => {
+                return Err("the ddp output needs a host, e.g. `ddp:192.168.1.60`".into());
+            }
+            Some(arg) => {
+                let (host, port) = parse_host(arg)?;
+                vec![DdpController {
+                    host,
+                    port,
+                    offset: 0,
+                    leds: None,
+                }]
+            }
+            None if settings.controllers.is_empty() => {
+                return Err("the ddp output needs a host, e.g. `ddp:192.168.1.60`, or \
+                     [[ddp.controllers]] in the app config"
+                    .into());
+            }
+            None => settings.controllers.clone(),
+        };
+
+        Ok(DdpOutput {
+            controllers,
+            chunk: settings.chunk,
+            routes: Vec::new(),
+            staged: Vec::new(),
+            sequence: 1,
+        })
+    }
+}
+
+/// Splits `<host>[:<port>]`, where the host may be a name, an IPv4 address,
+/// a bare IPv6 address, or a bracketed one followed by a port.
+fn parse_host(arg: &str) -> Result<(String, Option<u16>), String> {
+    if let Ok(addr) = arg.parse::<SocketAddr>() {
+        return Ok((addr.ip().to_string(), Some(addr.port())));
+    }
+    let unbracketed = arg
+        .strip_prefix('[')
+        .and_then(|arg| arg.strip_suffix(']'))
+        .unwrap_or(arg);
+    if let Ok(ip) = unbracketed.parse::<IpAddr>() {
+        return Ok((ip.to_string(), None));
+    }
+    match arg.split_once(':') {
+        Some((host, port)) if !host.is_empty() && !port.contains(':') => {
+            let port = port
+                .parse()
+                .map_err(|_| format!("invalid DDP port `{}`", port))?;
+            Ok((host.to_string(), Some(port)))
+        }
+        Some(_) => Err(format!("invalid DDP host `{}`", arg)),
+        None => Ok((arg.to_string(), None)),
+    }
+}
+
+impl LedOutput for DdpOutput {
+    fn open(&mut self, num_leds: usize) -> io::Result<()> {
+        let mut routes = Vec::new();
+        let mut start = 0;
+        for controller in &self.controllers {
+            let target = (controller.host.as_str(), controller.port.unwrap_or(PORT));
+            let addr = target.to_socket_addrs()?.next().ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{} has no address", controller.host),
+                )
+            })?;
+            let socket = match addr {
+                SocketAddr::V4(_) => UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?,
+                SocketAddr::V6(_) => UdpSocket::bind((Ipv6Addr::UNSPECIFIED, 0))?,
+            };
+            let len = controller
+                .leds
+                .unwrap_or(num_leds.saturating_sub(start))
+                .min(num_leds.saturating_sub(start));
+            println!(
+                "DDP output sending LEDs {} to {} to {}.",
+                start,
+                (start + len).saturating_sub(1),
+                addr
+            );
+            routes.push((addr, socket, start, len));
+            start += len;
+        }
+        if start < num_leds {
+            eprintln!(
+                "DDP output: the last {} LEDs have no controller.",
+                num_leds - start
+            );
+        }
+
+        self.routes = routes;
+        self.staged = vec![[0; 3]; num_leds];
+        Ok(())
+    }
+
+    fn write(&mut self, frame: &[Srgb]) -> io::Result<()> {
+        for (staged, color) in self.staged.iter_mut().zip(frame) {
+            *staged = to_rgb8(color);
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.routes.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "DDP output not open",
+            ));
+        }
+        let mut packet = Vec::with_capacity(HEADER_LEN + self.chunk * 3);
+        for (controller, (addr, socket, start, len)) in self.controllers.iter().zip(&self.routes) {
+            let (start, len) = (*start, *len);
+            let pixels = &self.staged[start..start + len];
+            let chunks = pixels.len().div_ceil(self.chunk);
+            for (i, chunk) in pixels.chunks(self.chunk).enumerate() {
+                let offset = (controller.offset + i * self.chunk) * 3;
+                let push = if i + 1 == chunks { FLAG_PUSH } else { 0 };
+
+                packet.clear();
+                packet.push(FLAG_VERSION_1 | push);
+                packet.push(self.sequence);
+                packet.push(TYPE_RGB24);
+                packet.push(DESTINATION_DISPLAY);
+                packet.extend_from_slice(&(offset as u32).to_be_bytes());
+                packet.extend_from_slice(&(chunk.len() as u16 * 3).to_be_bytes());
+                packet.extend_from_slice(chunk.as_flattened());
+                socket.send_to(&packet, *addr)?;
+            }
+        }
+        self.sequence = self.sequence % 15 + 1;
+        Ok(())
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        self.routes.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn host(name: &str, port: Option<u16>) -> Result<(String, Option<u16>), String> {
+        Ok((name.to_string(), port))
+    }
+
+    #[test]
+    fn parses_hosts_and_ports() {
+        assert_eq!(parse_host("wled.local"), host("wled.local", None));
+        assert_eq!(
+            parse_host("wled.local:4049"),
+            host("wled.local", Some(4049))
+        );
+        assert_eq!(parse_host("192.168.1.60"), host("192.168.1.60", None));
+        assert_eq!(
+            parse_host("192.168.1.60:4049"),
+            host("192.168.1.60", Some(4049))
+        );
+        assert!(parse_host("wled.local:port").is_err());
+        assert!(parse_host(":4049").is_err());
+    }
+
+    #[test]
+    fn parses_ipv6_with_and_without_a_port() {
+        assert_eq!(parse_host("::1"), host("::1", None));
+        assert_eq!(parse_host("fe80::1:2"), host("fe80::1:2", None));
+        assert_eq!(parse_host("[fe80::1]"), host("fe80::1", None));
+        assert_eq!(parse_host("[::1]:4049"), host("::1", Some(4049)));
+        assert!(parse_host("fe80::zz").is_err());
+    }
+
+    #[test]
+    fn sends_to_ipv4_and_ipv6_controllers() {
+        let v4 = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let Ok(v6) = UdpSocket::bind((Ipv6Addr::LOCALHOST, 0)) else {
+            // no IPv6 here
+            return;
+        };
+        let controller = |socket: &UdpSocket| DdpController {
+            host: socket.local_addr().unwrap().ip().to_string(),
+            port: Some(socket.local_addr().unwrap().port()),
+            offset: 0,
+            leds: Some(2),
+        };
+        let settings = DdpSettings {
+            controllers: vec![controller(&v4), controller(&v6)],
+            ..DdpSettings::default()
+        };
+        let mut output = DdpOutput::from_spec(None, &settings).unwrap();
+        output.open(4).unwrap();
+        let red = Srgb::new(1.0, 0.0, 0.0);
+        let blue = Srgb::new(0.0, 0.0, 1.0);
+        output.write(&[red, red, blue, blue]).unwrap();
+        output.flush().unwrap();
+
+        let mut buffer = [0; 64];
+        for (socket, rgb) in [(v4, [255, 0, 0]), (v6, [0, 0, 255])] {
+            socket
+                .set_read_timeout(Some(Duration::from_secs(2)))
+                .unwrap();
+            let len = socket.recv(&mut buffer).unwrap();
+            assert_eq!(len, HEADER_LEN + 6);
+            assert_eq!(buffer[0], FLAG_VERSION_1 | FLAG_PUSH);
+            assert_eq!(buffer[HEADER_LEN..len], [rgb, rgb].concat());
+        }
+    }
+}
@@ -0,0 +1,475 @@
+use std::{io, ops::Range};
+
+use sled::color::Srgb;
+
+use super::{from_spec, ws281x_pin, DdpSettings, GpioSettings, LedOutput};
+
+/// Which of the layout's LEDs a map entry takes.
+#[derive(Clone)]
+pub enum MapLeds {
+    /// One of the layout's line segments, counting from 0.
+    Segment(usize),
+    /// `count` LEDs from `start`. The start defaults to where the previous
+    /// entry ended, and the count to the rest of the layout.
+    Range {
+        start: Option<usize>,
+        count: Option<usize>,
+    },
+}
+
+/// Sends part of the layout to part of one output.
+#[derive(Clone)]
+pub struct MapEntry {
+    /// An output spec, as for `--output`. `ws281x:<pin>` entries on
+    /// different PWM channels share one controller.
+    pub output: String,
+    pub leds: MapLeds,
+    /// First LED on the output to write to. `None` carries on from the
+    /// previous entry for the same output, or starts at 0.
+    pub offset: Option<usize>,
+    /// Physical LEDs to leave dark before this entry's first one, such as
+    /// those hidden behind a corner.
+    pub skip: usize,
+    /// Writes the LEDs onto the output last to first, for strips wired
+    /// against the layout's direction.
+    pub reverse: bool,
+    /// Physical LEDs to keep dark, for pixels that are stuck on or flicker.
+    /// They're counted along the output from the first LED this entry writes
+    /// to, after `skip` and whichever way the entry runs, so each must be
+    /// less than the entry's LED count. The layout's LEDs don't shift to
+    /// fill the holes.
+    pub dead: Vec<usize>,
+}
+
+/// Where one entry's LEDs go.
+struct Route {
+    child: usize,
+    leds: Range<usize>,
+    offset: usize,
+    reverse: bool,
+    /// Absolute positions in the child's frame.
+    dead: Vec<usize>,
+}
+
+/// An output entries write to, while the map is being resolved.
+struct Target {
+    /// Set for ws281x pins, which are kept apart until they're combined.
+    pin: Option<i32>,
+    spec: String,
+    /// Where the last entry for it ended.
+    next_offset: usize,
+    /// The LED count it needs.
+    len: usize,
+    /// What its entries write to so far.
+    taken: Vec<Range<usize>>,
+}
+
+struct Child {
+    spec: String,
+    output: Box<dyn LedOutput>,
+    /// Staged colors in the output's own LED order.
+    frame: Vec<Srgb>,
+}
+
+/// Fans one logical layout out to several physical outputs, such as both
+/// ws281x channels and a few networked controllers.
+pub struct MappedOutput {
+    children: Vec<Child>,
+    routes: Vec<Route>,
+}
+
+impl MappedOutput {
+    /// Builds the outputs `entries` name, for a layout of `num_leds` LEDs
+    /// whose line segments cover `segments`. Two entries can't write to the
+    /// same LED of an output. Nothing is opened yet; `append` is passed on
+    /// to each child as for [`from_spec`].
+    pub fn new(
+        entries: &[MapEntry],
+        segments: &[Range<usize>],
+        num_leds: usize,
+        gpio: &GpioSettings,
+        ddp: &DdpSettings,
+        append: bool,
+    ) -> Result<Self, String> {
+        let mut targets: Vec<Target> = Vec::new();
+        let mut resolved = Vec::new();
+        let mut next = 0;
+        for entry in entries {
+            let leds = match entry.leds {
+                MapLeds::Segment(i) => segments.get(i).cloned().ok_or_else(|| {
+                    format!(
+                        "map entry for `{}`: the layout has no segment {}",
+                        entry.output, i
+                    )
+                })?,
+                MapLeds::Range { start, count } => {
+                    let start = start.unwrap_or(next);
+                    start..start + count.unwrap_or(num_leds.saturating_sub(start))
+                }
+            };
+            if leds.end > num_leds {
+                return Err(format!(
+                    "map entry for `{}`: LEDs {} to {} are past the layout's {} LEDs",
+                    entry.output,
+                    leds.start,
+                    leds.end - 1,
+                    num_leds
+                ));
+            }
+            next = leds.end;
+
+            let pin = match entry.output.split_once(':') {
+                Some(("ws281x", pin)) => Some(ws281x_pin(Some(pin), gpio)?),
+                None if entry.output == "ws281x" => Some(gpio.pin),
+                _ => None,
+            };
+            let target = match targets.iter().position(|target| {
+                target.pin == pin && (pin.is_some() || target.spec == entry.output)
+            }) {
+                Some(target) => target,
+                None => {
+                    targets.push(Target {
+                        pin,
+                        spec: entry.output.clone(),
+                        next_offset: 0,
+                        len: 0,
+                        taken: Vec::new(),
+                    });
+                    targets.len() - 1
+                }
+            };
+            let Target {
+                next_offset,
+                len,
+                taken,
+                ..
+            } = &mut targets[target];
+            let offset = entry.offset.unwrap_or(*next_offset) + entry.skip;
+            if let Some(dead) = entry.dead.iter().find(|&&dead| dead >= leds.len()) {
+                return Err(format!(
+                    "map entry for `{}`: dead LED {} is past its {} LEDs",
+                    entry.output,
+                    dead,
+                    leds.len()
+                ));
+            }
+            let span = offset..offset + leds.len();
+            if taken
+                .iter()
+                .any(|t| t.start < span.end && span.start < t.end)
+            {
+                return Err(format!(
+                    "map entry for `{}`: its LEDs {} to {} on the output overlap an \
+                     earlier entry's",
+                    entry.output,
+                    span.start,
+                    span.end.saturating_sub(1)
+                ));
+            }
+            taken.push(span.clone());
+            *next_offset = span.end;
+            *len = (*len).max(span.end);
+            resolved.push((target, leds, offset, entry));
+        }
+
+        // every ws281x pin goes to one controller, after the other outputs,
+        // with each pin's LEDs following the previous pin's in its frame
+        let ws281x = targets.iter().filter(|target| target.pin.is_none()).count();
+        let mut children = Vec::new();
+        let mut placement = Vec::new();
+        let mut strips = Vec::new();
+        let mut strips_len = 0;
+        for target in &targets {
+            match target.pin {
+                Some(pin) => {
+                    placement.push((ws281x, strips_len));
+                    strips.push((pin, target.len));
+                    strips_len += target.len;
+                }
+                None => {
+                    placement.push((children.len(), 0));
+                    children.push(Child {
+                        spec: target.spec.clone(),
+                        output: from_spec(&target.spec, gpio, ddp, append)?,
+                        frame: vec![Srgb::new(0.0, 0.0, 0.0); target.len],
+                    });
+                }
+            }
+        }
+        if !strips.is_empty() {
+            let pins: Vec<String> = strips.iter().map(|(pin, _)| pin.to_string()).collect();
+            children.push(Child {
+                spec: format!("ws281x:{}", pins.join("+")),
+                output: ws281x_strips(gpio, strips)?,
+                frame: vec![Srgb::new(0.0, 0.0, 0.0); strips_len],
+            });
+        }
+
+        let routes = resolved
+            .into_iter()
+            .map(|(target, leds, offset, entry)| {
+                let (child, base) = placement[target];
+                Route {
+                    child,
+                    leds,
+                    offset: base + offset,
+                    reverse: entry.reverse,
+                    dead: entry.dead.iter().map(|dead| base + offset + dead).collect(),
+                }
+            })
+            .collect();
+        Ok(MappedOutput { children, routes })
+    }
+}
+
+#[cfg(feature = "ws281x")]
+fn ws281x_strips(
+    gpio: &GpioSettings,
+    strips: Vec<(i32, usize)>,
+) -> Result<Box<dyn LedOutput>, String> {
+    Ok(Box::new(super::Ws281xOutput::with_strips(gpio, strips)?))
+}
+
+#[cfg(not(feature = "ws281x"))]
+fn ws281x_strips(
+    _gpio: &GpioSettings,
+    _strips: Vec<(i32, usize)>,
+) -> Result<Box<dyn LedOutput>, String> {
+    Err("this build has no ws281x support; rebuild with `--features ws281x`".into())
+}
+
+impl LedOutput for MappedOutput {
+    fn open(&mut self, _num_leds: usize) -> io::Result<()> {
+        for route in &self.routes {
+            let child = &self.children[route.child];
+            println!(
+                "Map: LEDs {} to {} go to `{}` from LED {}{}{}.",
+                route.leds.start,
+                route.leds.end.saturating_sub(1),
+                child.spec,
+                route.offset,
+                if route.reverse { ", reversed" } else { "" },
+                match route.dead.len() {
+                    0 => String::new(),
+                    n => format!(", {} dead", n),
+                }
+            );
+        }
+        for child in &mut self.children {
+            child.output.open(child.frame.len())?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, frame: &[Srgb]) -> io::Result<()> {
+        for route in &self.routes {
+            let Some(colors) = frame.get(route.leds.clone()) else {
+                continue;
+            };
+            let child = &mut self.children[route.child].frame;
+            let target = &mut child[route.offset..][..colors.len()];
+            if route.reverse {
+                for (led, color) in target.iter_mut().rev().zip(colors) {
+                    *led = *color;
+                }
+            } else {
+                target.copy_from_slice(colors);
+            }
+            for &dead in &route.dead {
+                child[dead] = Srgb::new(0.0, 0.0, 0.0);
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        for child in &mut self.children {
+            child.output.write(&child.frame)?;
+            child.output.flush()?;
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        // close everything, even if one fails
+        let mut result = Ok(());
+        for child in &mut self.children {
+            result = result.and(child.output.close());
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{to_rgb8, StripType};
+
+    const GPIO: GpioSettings = GpioSettings {
+        pin: 18,
+        strip_type: StripType::Ws2811Grb,
+    };
+
+    fn entry(output: &str, start: usize, count: usize) -> MapEntry {
+        MapEntry {
+            output: output.into(),
+            leds: MapLeds::Range {
+                start: Some(start),
+                count: Some(count),
+            },
+            offset: None,
+            skip: 0,
+            reverse: false,
+            dead: Vec::new(),
+        }
+    }
+
+    fn map(entries: &[MapEntry], num_leds: usize) -> Result<MappedOutput, String> {
+        let segments = [0..3, 3..num_leds];
+        MappedOutput::new(
+            entries,
+            &segments,
+            num_leds,
+            &GPIO,
+            &DdpSettings::default(),
+            false,
+        )
+    }
+
+    /// Writes a frame where LED `i` has a red level of `i + 1`, and reads
+    /// back which layout LED each of a child's LEDs got, if any.
+    fn written(output: &mut MappedOutput, num_leds: usize) -> Vec<Vec<Option<usize>>> {
+        let frame: Vec<Srgb> = (0..num_leds)
+            .map(|i| Srgb::new((i + 1) as f32 / 255.0, 0.0, 0.0))
+            .collect();
+        output.write(&frame).unwrap();
+        output
+            .children
+            .iter()
+            .map(|child| {
+                let leds = child.frame.iter().map(|color| {
+                    let [red, _, _] = to_rgb8(color);
+                    (red as usize).checked_sub(1)
+                });
+                leds.collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn offsets_carry_on_per_output() {
+        let entries = [
+            entry("record:a.csv", 0, 2),
+            entry("record:b.csv", 2, 2),
+            entry("record:a.csv", 4, 2),
+        ];
+        let mut output = map(&entries, 6).unwrap();
+        assert_eq!(
+            written(&mut output, 6),
+            [
+                [Some(0), Some(1), Some(4), Some(5)].to_vec(),
+                [Some(2), Some(3)].to_vec(),
+            ]
+        );
+    }
+
+    #[test]
+    fn ranges_default_to_following_the_previous_entry() {
+        let mut entries = [entry("record:a.csv", 0, 0), entry("record:b.csv", 0, 0)];
+        entries[0].leds = MapLeds::Segment(0);
+        entries[1].leds = MapLeds::Range {
+            start: None,
+            count: None,
+        };
+        entries[1].offset = Some(1);
+        let mut output = map(&entries, 5).unwrap();
+        assert_eq!(
+            written(&mut output, 5),
+            [
+                [Some(0), Some(1), Some(2)].to_vec(),
+                [None, Some(3), Some(4)].to_vec()
+            ]
+        );
+    }
+
+    #[test]
+    fn reverse_writes_last_to_first() {
+        let mut entries = [entry("record:a.csv", 0, 2), entry("record:a.csv", 2, 3)];
+        entries[1].reverse = true;
+        let mut output = map(&entries, 5).unwrap();
+        assert_eq!(
+            written(&mut output, 5),
+            [[Some(0), Some(1), Some(4), Some(3), Some(2)].to_vec()]
+        );
+    }
+
+    #[test]
+    fn skip_leaves_a_gap() {
+        let mut entries = [entry("record:a.csv", 0, 2), entry("record:a.csv", 2, 2)];
+        entries[1].skip = 2;
+        let mut output = map(&entries, 4).unwrap();
+        assert_eq!(
+            written(&mut output, 4),
+            [[Some(0), Some(1), None, None, Some(2), Some(3)].to_vec()]
+        );
+    }
+
+    #[test]
+    fn dead_counts_from_after_the_skip_even_when_reversed() {
+        let mut entries = [entry("record:a.csv", 0, 4)];
+        entries[0].skip = 1;
+        entries[0].reverse = true;
+        entries[0].dead = vec![0, 2];
+        let mut output = map(&entries, 4).unwrap();
+        assert_eq!(
+            written(&mut output, 4),
+            [[None, None, Some(2), None, Some(0)].to_vec()]
+        );
+
+        // the last of its 4 LEDs is 3
+        entries[0].dead = vec![4];
+        assert!(map(&entries, 4).is_err());
+    }
+
+    #[test]
+    fn overlapping_entries_are_rejected() {
+        let mut entries = [entry("record:a.csv", 0, 3), entry("record:a.csv", 3, 2)];
+        entries[1].offset = Some(2);
+        assert!(map(&entries, 5).is_err());
+
+        // the same LEDs on different outputs are fine
+        entries[1].output = "record:b.csv".into();
+        assert!(map(&entries, 5).is_ok());
+    }
+
+    #[test]
+    fn entries_past_the_layout_are_rejected() {
+        assert!(map(&[entry("record:a.csv", 3, 3)], 5).is_err());
+        let mut entry = entry("record:a.csv", 0, 0);
+        entry.leds = MapLeds::Segment(2);
+        assert!(map(&[entry], 5).is_err());
+    }
+
+    #[cfg(feature = "ws281x")]
+    #[test]
+    fn ws281x_pins_share_one_controller() {
+        let entries = [
+            entry("ws281x", 0, 3),
+            entry("record:a.csv", 3, 1),
+            entry("ws281x:13", 4, 2),
+            entry("ws281x:18", 6, 1),
+        ];
+        let mut output = map(&entries, 7).unwrap();
+        let specs: Vec<&str> = output.children.iter().map(|c| c.spec.as_str()).collect();
+        assert_eq!(specs, ["record:a.csv", "ws281x:18+13"]);
+        // each pin's LEDs follow the previous pin's in the controller's frame
+        assert_eq!(
+            written(&mut output, 7)[1],
+            [Some(0), Some(1), Some(2), Some(6), Some(4), Some(5)]
+        );
+
+        // 18 and 12 are both on the first PWM channel
+        let entries = [entry("ws281x:18", 0, 3), entry("ws281x:12", 3, 2)];
+        assert!(map(&entries, 5).is_err());
+    }
+}
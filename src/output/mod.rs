@@ -0,0 +1,135 @@
+use std::io;
+
+use clap::ValueEnum;
+use serde::Deserialize;
+use sled::color::Srgb;
+
+mod artnet;
+mod ddp;
+mod mapped;
+mod null;
+mod pipeline;
+mod power;
+mod recording;
+#[cfg(feature = "ws281x")]
+mod ws281x;
+
+pub use artnet::ArtNetOutput;
+pub use ddp::{DdpController, DdpOutput, DdpSettings};
+pub use mapped::{MapEntry, MapLeds, MappedOutput};
+pub use null::NullOutput;
+pub use pipeline::{ClampMode, ColorOrder, ColorPipeline};
+pub use power::{PowerLimiter, PowerReport};
+pub use recording::RecordingOutput;
+#[cfg(feature = "ws281x")]
+pub use ws281x::Ws281xOutput;
+
+/// A destination for rendered frames, such as a physical strip or a file.
+///
+/// The render loop only ever talks to this trait, so the same effects can run
+/// on a Pi, a laptop, or in tests.
+pub trait LedOutput {
+    /// Prepares the output to receive frames of `num_leds` colors.
+    fn open(&mut self, num_leds: usize) -> io::Result<()>;
+    /// Stages one frame of colors, in sled index order.
+    fn write(&mut self, frame: &[Srgb]) -> io::Result<()>;
+    /// Pushes the most recently written frame out to the hardware.
+    fn flush(&mut self) -> io::Result<()>;
+    /// Releases any resources held by the output.
+    fn close(&mut self) -> io::Result<()>;
+}
+
+/// Chipset and wire color order of a ws281x strip.
+#[derive(Clone, Copy, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StripType {
+    Ws2811Rgb,
+    Ws2811Rbg,
+    Ws2811Grb,
+    Ws2811Gbr,
+    Ws2811Brg,
+    Ws2811Bgr,
+    Sk6812Rgbw,
+    Sk6812Grbw,
+}
+
+/// Hardware settings used when building a `ws281x` output.
+#[derive(Clone)]
+#[cfg_attr(not(feature = "ws281x"), allow(dead_code))]
+pub struct GpioSettings {
+    pub pin: i32,
+    pub strip_type: StripType,
+}
+
+/// Builds an output from a short spec string, e.g. `ws281x`, `ws281x:13`,
+/// `null`, `record:frames.csv`, `artnet:192.168.1.50/0` or
+/// `ddp:192.168.1.60`. With `append`, a `record` output adds to its file
+/// rather than starting it over, for outputs rebuilt on a layout reload.
+#[cfg_attr(not(feature = "ws281x"), allow(unused_variables))]
+pub fn from_spec(
+    spec: &str,
+    gpio: &GpioSettings,
+    ddp: &DdpSettings,
+    append: bool,
+) -> Result<Box<dyn LedOutput>, String> {
+    let (kind, arg) = match spec.split_once(':') {
+        Some((kind, arg)) => (kind, Some(arg)),
+        None => (spec, None),
+    };
+
+    match (kind, arg) {
+        #[cfg(feature = "ws281x")]
+        ("ws281x", pin) => {
+            let pin = ws281x_pin(pin, gpio)?;
+            Ok(Box::new(Ws281xOutput::new(&GpioSettings { pin, ..*gpio })))
+        }
+        ("null", None) => Ok(Box::new(NullOutput::new())),
+        ("record", Some(path)) if !path.is_empty() => {
+            Ok(Box::new(RecordingOutput::new(path, append)))
+        }
+        ("record", _) => Err("the record output needs a path, e.g. `record:frames.bin`".into()),
+        ("artnet", Some(arg)) => Ok(Box::new(ArtNetOutput::from_spec(arg)?)),
+        ("ddp", arg) => Ok(Box::new(DdpOutput::from_spec(arg, ddp)?)),
+        ("artnet", None) => {
+            Err("the artnet output needs a host, e.g. `artnet:192.168.1.50`".into())
+        }
+        #[cfg(not(feature = "ws281x"))]
+        ("ws281x", _) => {
+            Err("this build has no ws281x support; rebuild with `--features ws281x`".into())
+        }
+        _ => Err(format!("unknown output `{}`", spec)),
+    }
+}
+
+/// The pin named after `ws281x:` in an output spec, or the configured one.
+fn ws281x_pin(arg: Option<&str>, gpio: &GpioSettings) -> Result<i32, String> {
+    match arg {
+        Some(pin) => pin
+            .parse()
+            .map_err(|_| format!("invalid ws281x pin `{}`", pin)),
+        None => Ok(gpio.pin),
+    }
+}
+
+/// Writes an all-black frame of `num_leds` and closes the output, so the strip
+/// doesn't keep showing the last frame after the process exits.
+pub fn blackout_and_close(output: &mut dyn LedOutput, num_leds: usize) -> io::Result<()> {
+    let black = vec![Srgb::new(0.0, 0.0, 0.0); num_leds];
+    let blackout = output.write(&black).and_then(|_| output.flush());
+    let close = output.close();
+    blackout.and(close)
+}
+
+/// The output used when none is requested explicitly.
+pub fn default_spec() -> &'static str {
+    if cfg!(feature = "ws281x") {
+        "ws281x"
+    } else {
+        "null"
+    }
+}
+
+/// Quantizes a color to 8 bits per channel, clamping anything out of range.
+pub fn to_rgb8(color: &Srgb) -> [u8; 3] {
+    [color.red, color.green, color.blue].map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
@@ -0,0 +1,37 @@
+use std::io;
+
+use sled::color::Srgb;
+
+use super::LedOutput;
+
+/// Discards every frame. Useful for running effects without any hardware.
+pub struct NullOutput {
+    frames_written: usize,
+}
+
+impl NullOutput {
+    pub fn new() -> Self {
+        NullOutput { frames_written: 0 }
+    }
+}
+
+impl LedOutput for NullOutput {
+    fn open(&mut self, num_leds: usize) -> io::Result<()> {
+        println!("Null output opened for {} LEDs.", num_leds);
+        Ok(())
+    }
+
+    fn write(&mut self, _frame: &[Srgb]) -> io::Result<()> {
+        self.frames_written += 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        println!("Null output closed after {} frames.", self.frames_written);
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+use sled::color::Srgb;
+
+/// How colors brighter than 1.0 (which additive effects like `ripples`
+/// routinely produce) are brought back into range.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClampMode {
+    /// Clip each channel to 1.0 on its own. Bright colors wash out to white.
+    Clip,
+    /// Scale the whole color down until its brightest channel is 1.0,
+    /// keeping the hue.
+    Normalize,
+    /// Reinhard tonemapping, `c / (1 + c)` per channel. Compresses everything,
+    /// so it dims colors that were already in range.
+    Reinhard,
+}
+
+/// The order in which channels are sent down the wire.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorOrder {
+    Rgb,
+    Rbg,
+    Grb,
+    Gbr,
+    Brg,
+    Bgr,
+}
+
+/// Turns the colors a driver produced into the values an output should show.
+///
+/// Stages run in order: range clamping, gamma, white balance, global
+/// brightness, then channel reordering.
+#[derive(Clone)]
+pub struct ColorPipeline {
+    pub clamp: ClampMode,
+    /// Exponent applied to each channel. 1.0 leaves colors untouched, ~2.2
+    /// makes fades look even on most strips.
+    pub gamma: f32,
+    /// Per-channel multipliers, for strips whose white is tinted.
+    pub white_balance: [f32; 3],
+    /// Global brightness, 0.0 to 1.0.
+    pub brightness: f32,
+    pub order: ColorOrder,
+}
+
+impl Default for ColorPipeline {
+    fn default() -> Self {
+        ColorPipeline {
+            clamp: ClampMode::Clip,
+            gamma: 1.0,
+            white_balance: [1.0, 1.0, 1.0],
+            brightness: 1.0,
+            order: ColorOrder::Rgb,
+        }
+    }
+}
+
+impl ColorPipeline {
+    pub fn apply(&self, frame: &mut [Srgb]) {
+        for color in frame {
+            *color = self.process(*color);
+        }
+    }
+
+    pub fn process(&self, color: Srgb) -> Srgb {
+        let mut channels = clamp([color.red, color.green, color.blue], self.clamp);
+        for (channel, balance) in channels.iter_mut().zip(self.white_balance) {
+            if self.gamma != 1.0 {
+                *channel = channel.powf(self.gamma);
+            }
+            *channel *= balance * self.brightness;
+        }
+
+        let [r, g, b] = channels;
+        let [first, second, third] = match self.order {
+            ColorOrder::Rgb => [r, g, b],
+            ColorOrder::Rbg => [r, b, g],
+            ColorOrder::Grb => [g, r, b],
+            ColorOrder::Gbr => [g, b, r],
+            ColorOrder::Brg => [b, r, g],
+            ColorOrder::Bgr => [b, g, r],
+        };
+        Srgb::new(first, second, third)
+    }
+}
+
+fn clamp(channels: [f32; 3], mode: ClampMode) -> [f32; 3] {
+    // effects can also undershoot, e.g. warpspeed's fade with a large delta
+    let channels = channels.map(|c| if c.is_nan() { 0.0 } else { c.max(0.0) });
+    match mode {
+        ClampMode::Clip => channels.map(|c| c.min(1.0)),
+        ClampMode::Normalize => {
+            let max = channels[0].max(channels[1]).max(channels[2]);
+            if max > 1.0 {
+                channels.map(|c| c / max)
+            } else {
+                channels
+            }
+        }
+        ClampMode::Reinhard => channels.map(|c| c / (1.0 + c)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgb(color: Srgb) -> [f32; 3] {
+        [color.red, color.green, color.blue]
+    }
+
+    #[test]
+    fn clip_clamps_each_channel() {
+        assert_eq!(clamp([2.0, 0.5, -1.0], ClampMode::Clip), [1.0, 0.5, 0.0]);
+        assert_eq!(
+            clamp([f32::NAN, 1.0, 0.25], ClampMode::Clip),
+            [0.0, 1.0, 0.25]
+        );
+    }
+
+    #[test]
+    fn normalize_keeps_the_hue() {
+        assert_eq!(
+            clamp([4.0, 2.0, 1.0], ClampMode::Normalize),
+            [1.0, 0.5, 0.25]
+        );
+        // in range already, so untouched
+        assert_eq!(
+            clamp([0.5, 0.25, 1.0], ClampMode::Normalize),
+            [0.5, 0.25, 1.0]
+        );
+        assert_eq!(
+            clamp([f32::NAN, -2.0, 2.0], ClampMode::Normalize),
+            [0.0, 0.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn reinhard_compresses_everything() {
+        assert_eq!(
+            clamp([1.0, 3.0, 0.0], ClampMode::Reinhard),
+            [0.5, 0.75, 0.0]
+        );
+        assert_eq!(
+            clamp([-1.0, f32::NAN, 1.0], ClampMode::Reinhard),
+            [0.0, 0.0, 0.5]
+        );
+    }
+
+    #[test]
+    fn reorders_channels_last() {
+        let color = Srgb::new(0.2, 0.4, 0.8);
+        let orders = [
+            (ColorOrder::Rgb, [0.2, 0.4, 0.8]),
+            (ColorOrder::Rbg, [0.2, 0.8, 0.4]),
+            (ColorOrder::Grb, [0.4, 0.2, 0.8]),
+            (ColorOrder::Gbr, [0.4, 0.8, 0.2]),
+            (ColorOrder::Brg, [0.8, 0.2, 0.4]),
+            (ColorOrder::Bgr, [0.8, 0.4, 0.2]),
+        ];
+        for (order, expected) in orders {
+            let pipeline = ColorPipeline {
+                order,
+                ..ColorPipeline::default()
+            };
+            assert_eq!(rgb(pipeline.process(color)), expected);
+        }
+    }
+
+    #[test]
+    fn applies_gamma_balance_and_brightness_in_order() {
+        let pipeline = ColorPipeline {
+            gamma: 2.0,
+            white_balance: [1.0, 0.5, 1.0],
+            brightness: 0.5,
+            order: ColorOrder::Grb,
+            ..ColorPipeline::default()
+        };
+        // red clips to 1 before gamma; green is balanced after it
+        assert_eq!(
+            rgb(pipeline.process(Srgb::new(3.0, 0.5, 0.0))),
+            [0.0625, 0.5, 0.0]
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use sled::color::Srgb;
+
+/// Estimates how much current a frame will draw and dims it to stay within
+/// a budget, so additive effects can't brown out the power supply.
+///
+/// Each LED is modeled as a fixed idle draw plus a linear draw per channel,
+/// which is close enough for WS281x-style strips.
+#[derive(Clone)]
+pub struct PowerLimiter {
+    /// Maximum total draw in milliamps, or `None` to only estimate.
+    pub budget_ma: Option<f32>,
+    /// Draw of one channel at full brightness.
+    pub ma_per_channel: f32,
+    /// Draw of one LED showing black.
+    pub idle_ma_per_led: f32,
+}
+
+#[derive(Clone, Copy)]
+pub struct PowerReport {
+    /// Estimated draw of the frame as the effect produced it.
+    pub estimated_ma: f32,
+    /// Factor the frame was scaled by to fit the budget, 1.0 if it already did.
+    pub scale: f32,
+}
+
+impl Default for PowerLimiter {
+    fn default() -> Self {
+        PowerLimiter {
+            budget_ma: None,
+            ma_per_channel: 20.0,
+            idle_ma_per_led: 1.0,
+        }
+    }
+}
+
+impl PowerLimiter {
+    /// Estimates the draw of `frame` and scales it down in place if it
+    /// exceeds the budget. Expects colors already in the 0.0 to 1.0 range.
+    pub fn limit(&self, frame: &mut [Srgb]) -> PowerReport {
+        let idle_ma = self.idle_ma_per_led * frame.len() as f32;
+        let active_ma = frame
+            .iter()
+            .map(|c| (c.red + c.green + c.blue) * self.ma_per_channel)
+            .sum::<f32>();
+
+        let scale = match self.budget_ma {
+            Some(budget) if idle_ma + active_ma > budget && active_ma > 0.0 => {
+                ((budget - idle_ma) / active_ma).clamp(0.0, 1.0)
+            }
+            _ => 1.0,
+        };
+
+        if scale < 1.0 {
+            for color in frame.iter_mut() {
+                *color = Srgb::new(color.red * scale, color.green * scale, color.blue * scale);
+            }
+        }
+
+        PowerReport {
+            estimated_ma: idle_ma + active_ma,
+            scale,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(budget_ma: Option<f32>) -> PowerLimiter {
+        PowerLimiter {
+            budget_ma,
+            ..PowerLimiter::default()
+        }
+    }
+
+    #[test]
+    fn only_estimates_without_a_budget() {
+        let mut frame = [Srgb::new(1.0, 1.0, 1.0); 10];
+        let report = limiter(None).limit(&mut frame);
+        assert_eq!(report.estimated_ma, 610.0);
+        assert_eq!(report.scale, 1.0);
+        assert_eq!(frame[0], Srgb::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn scales_the_active_draw_to_fit() {
+        // 10 mA idle plus 600 mA lit, with 310 mA to spend
+        let mut frame = [Srgb::new(1.0, 1.0, 1.0); 10];
+        let report = limiter(Some(310.0)).limit(&mut frame);
+        assert_eq!(report.estimated_ma, 610.0);
+        assert_eq!(report.scale, 0.5);
+        assert_eq!(frame[9], Srgb::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn goes_dark_when_idle_draw_alone_is_over_budget() {
+        let mut frame = [Srgb::new(1.0, 0.0, 0.0); 10];
+        let report = limiter(Some(5.0)).limit(&mut frame);
+        assert_eq!(report.scale, 0.0);
+        assert_eq!(frame[0], Srgb::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn leaves_a_black_frame_alone() {
+        // nothing lit to scale, even though idle draw is over budget
+        let mut frame = [Srgb::new(0.0, 0.0, 0.0); 10];
+        let report = limiter(Some(5.0)).limit(&mut frame);
+        assert_eq!(report.estimated_ma, 10.0);
+        assert_eq!(report.scale, 1.0);
+    }
+}
@@ -0,0 +1,247 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use sled::color::Srgb;
+
+use super::{to_rgb8, LedOutput};
+
+const MAGIC: &[u8; 8] = b"SLEDREC1";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// `SLEDREC1`, then `u32` LED count and `u64` unix start time in ms as a
+    /// header. Each frame is a `u32` ms offset followed by 3 bytes per LED.
+    /// All integers are little endian.
+    Binary,
+    /// A `#` comment header, then one `time_ms,rrggbb,rrggbb,...` row per frame.
+    Csv,
+}
+
+/// Writes every frame to a file so a run can be inspected or diffed later
+/// without any hardware attached.
+///
+/// Paths ending in `.csv` are written as text, anything else as compact binary.
+/// Each time the output is opened it writes a new header, so a recording that
+/// spans a change in LED count holds one section per count. The first open
+/// starts the file over unless the output was made to append to it.
+pub struct RecordingOutput {
+    path: PathBuf,
+    format: Format,
+    append: bool,
+    writer: Option<BufWriter<File>>,
+    started: Instant,
+    staged: Vec<[u8; 3]>,
+}
+
+impl RecordingOutput {
+    /// A recording to `path`. With `append` it adds to whatever the file
+    /// already holds, as a layout reload that rebuilds the output wants.
+    pub fn new(path: impl Into<PathBuf>, append: bool) -> Self {
+        let path = path.into();
+        let format = match path.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => Format::Csv,
+            _ => Format::Binary,
+        };
+
+        RecordingOutput {
+            path,
+            format,
+            append,
+            writer: None,
+            started: Instant::now(),
+            staged: Vec::new(),
+        }
+    }
+
+    fn writer(&mut self) -> io::Result<&mut BufWriter<File>> {
+        self.writer
+            .as_mut()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "recording not open"))
+    }
+}
+
+impl LedOutput for RecordingOutput {
+    fn open(&mut self, num_leds: usize) -> io::Result<()> {
+        let reopened = self.append;
+        let file = if reopened {
+            OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(&self.path)?
+        } else {
+            File::create(&self.path)?
+        };
+        let mut writer = BufWriter::new(file);
+        let start_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+
+        match self.format {
+            Format::Binary => {
+                writer.write_all(MAGIC)?;
+                writer.write_all(&(num_leds as u32).to_le_bytes())?;
+                writer.write_all(&start_ms.to_le_bytes())?;
+            }
+            Format::Csv => {
+                writeln!(writer, "# started_unix_ms={} leds={}", start_ms, num_leds)?;
+            }
+        }
+
+        if reopened {
+            println!(
+                "Recording {} LEDs on to the end of {}.",
+                num_leds,
+                self.path.display()
+            );
+        } else {
+            println!("Recording frames to {}.", self.path.display());
+        }
+        self.writer = Some(writer);
+        self.append = true;
+        self.started = Instant::now();
+        self.staged = vec![[0; 3]; num_leds];
+        Ok(())
+    }
+
+    fn write(&mut self, frame: &[Srgb]) -> io::Result<()> {
+        for (staged, color) in self.staged.iter_mut().zip(frame) {
+            *staged = to_rgb8(color);
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let time_ms = self.started.elapsed().as_millis() as u32;
+        let format = self.format;
+        let staged = std::mem::take(&mut self.staged);
+        let writer = self.writer()?;
+
+        let result = match format {
+            Format::Binary => writer
+                .write_all(&time_ms.to_le_bytes())
+                .and_then(|_| writer.write_all(staged.as_flattened())),
+            Format::Csv => write_csv_row(writer, time_ms, &staged),
+        };
+
+        self.staged = staged;
+        result
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+fn write_csv_row(writer: &mut impl Write, time_ms: u32, colors: &[[u8; 3]]) -> io::Result<()> {
+    write!(writer, "{}", time_ms)?;
+    for [r, g, b] in colors {
+        write!(writer, ",{:02x}{:02x}{:02x}", r, g, b)?;
+    }
+    writeln!(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("recording-{}-{}", std::process::id(), name))
+    }
+
+    fn record(path: &Path, frames: &[[Srgb; 2]]) {
+        let mut output = RecordingOutput::new(path, false);
+        output.open(2).unwrap();
+        for frame in frames {
+            output.write(frame).unwrap();
+            output.flush().unwrap();
+        }
+        output.close().unwrap();
+    }
+
+    const FRAMES: [[Srgb; 2]; 2] = [
+        [Srgb::new(1.0, 0.0, 0.0), Srgb::new(0.0, 0.5, 1.0)],
+        [Srgb::new(0.0, 0.0, 0.0), Srgb::new(2.0, -1.0, 0.2)],
+    ];
+
+    #[test]
+    fn csv_has_a_row_per_frame() {
+        let path = temp_path("frames.csv");
+        record(&path, &FRAMES);
+        let text = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("# started_unix_ms="));
+        assert!(lines[0].ends_with(" leds=2"));
+        let colors = |line: &str| line.split(',').skip(1).collect::<Vec<_>>().join(",");
+        assert_eq!(colors(lines[1]), "ff0000,0080ff");
+        assert_eq!(colors(lines[2]), "000000,ff0033");
+    }
+
+    #[test]
+    fn binary_has_a_header_then_fixed_size_frames() {
+        let path = temp_path("frames.bin");
+        record(&path, &FRAMES);
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(&bytes[..8], MAGIC);
+        assert_eq!(u32::from_le_bytes(bytes[8..12].try_into().unwrap()), 2);
+        let frames: Vec<&[u8]> = bytes[20..].chunks(4 + 2 * 3).collect();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(&frames[0][4..], [255, 0, 0, 0, 128, 255]);
+        assert_eq!(&frames[1][4..], [0, 0, 0, 255, 0, 51]);
+    }
+
+    #[test]
+    fn reopening_appends_a_new_header() {
+        let path = temp_path("reopened.csv");
+        record(&path, &FRAMES[..1]);
+        // a layout reload builds a new output that appends, then may reopen it
+        let mut output = RecordingOutput::new(&path, true);
+        output.open(3).unwrap();
+        output.write(&[Srgb::new(0.0, 0.0, 1.0); 3]).unwrap();
+        output.flush().unwrap();
+        output.close().unwrap();
+        output.open(1).unwrap();
+        output.close().unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].ends_with(" leds=2"));
+        assert!(lines[1].ends_with(",ff0000,0080ff"));
+        assert!(lines[2].ends_with(" leds=3"));
+        assert!(lines[3].ends_with(",0000ff,0000ff,0000ff"));
+        assert!(lines[4].ends_with(" leds=1"));
+    }
+
+    #[test]
+    fn a_new_recording_starts_the_file_over() {
+        let path = temp_path("restarted.csv");
+        record(&path, &FRAMES);
+        record(&path, &FRAMES[..1]);
+        let text = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(text.lines().count(), 2);
+    }
+
+    #[test]
+    fn flush_before_open_fails() {
+        let mut output = RecordingOutput::new(temp_path("unopened.csv"), false);
+        assert!(output.flush().is_err());
+    }
+}
@@ -0,0 +1,138 @@
+use std::io;
+
+use rs_ws281x::{ChannelBuilder, Controller, ControllerBuilder};
+use sled::color::Srgb;
+
+use super::{to_rgb8, GpioSettings, LedOutput, StripType};
+
+/// Drives WS281x strips from the Pi's GPIO through `rs_ws281x`: one strip,
+/// or two on pins backed by different PWM channels, such as 18 and 13.
+pub struct Ws281xOutput {
+    strip_type: StripType,
+    /// Each strip's pin and LED count, in the order their LEDs appear in a
+    /// frame. A `None` count takes whatever `open` is given.
+    strips: Vec<(i32, Option<usize>)>,
+    /// The hardware channel and LED count each strip ended up with.
+    channels: Vec<(usize, usize)>,
+    controller: Option<Controller>,
+}
+
+impl Ws281xOutput {
+    pub fn new(settings: &GpioSettings) -> Self {
+        Ws281xOutput {
+            strip_type: settings.strip_type,
+            strips: vec![(settings.pin, None)],
+            channels: Vec::new(),
+            controller: None,
+        }
+    }
+
+    /// Drives a strip of fixed length on each pin, with frames holding the
+    /// first strip's LEDs, then the second's.
+    pub fn with_strips(settings: &GpioSettings, strips: Vec<(i32, usize)>) -> Result<Self, String> {
+        let mut channels: Vec<usize> = Vec::new();
+        for &(pin, _) in &strips {
+            let channel = channel_for(pin);
+            if channels.contains(&channel) {
+                return Err(format!(
+                    "ws281x pin {} shares a PWM channel with another pin in use",
+                    pin
+                ));
+            }
+            channels.push(channel);
+        }
+
+        Ok(Ws281xOutput {
+            strip_type: settings.strip_type,
+            strips: strips
+                .into_iter()
+                .map(|(pin, leds)| (pin, Some(leds)))
+                .collect(),
+            channels: Vec::new(),
+            controller: None,
+        })
+    }
+
+    fn controller(&mut self) -> io::Result<&mut Controller> {
+        self.controller.as_mut().ok_or_else(not_open)
+    }
+}
+
+fn not_open() -> io::Error {
+    io::Error::new(io::ErrorKind::NotConnected, "ws281x output not open")
+}
+
+/// The PWM channel that drives `pin`. Pins 13 and 19 are on the second;
+/// 18, 12 and the SPI and PCM pins use the first.
+fn channel_for(pin: i32) -> usize {
+    match pin {
+        13 | 19 | 41 | 45 | 53 => 1,
+        _ => 0,
+    }
+}
+
+impl LedOutput for Ws281xOutput {
+    fn open(&mut self, num_leds: usize) -> io::Result<()> {
+        let mut builder = ControllerBuilder::new();
+        self.channels.clear();
+        for &(pin, leds) in &self.strips {
+            let channel = channel_for(pin);
+            let count = leds.unwrap_or(num_leds);
+            builder.channel(
+                channel,
+                ChannelBuilder::new()
+                    .pin(pin)
+                    .count(count as i32)
+                    .strip_type(self.strip_type.into())
+                    // brightness is handled by the color pipeline instead
+                    .brightness(255)
+                    .build(),
+            );
+            self.channels.push((channel, count));
+        }
+
+        self.controller = Some(builder.build().map_err(ws281x_error)?);
+        Ok(())
+    }
+
+    fn write(&mut self, frame: &[Srgb]) -> io::Result<()> {
+        let controller = self.controller.as_mut().ok_or_else(not_open)?;
+        let mut colors = frame.iter();
+        for &(channel, count) in &self.channels {
+            let leds = controller.leds_mut(channel);
+            for (led, color) in leds.iter_mut().zip(colors.by_ref().take(count)) {
+                let [r, g, b] = to_rgb8(color);
+                *led = [r, g, b, 0];
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.controller()?.render().map_err(ws281x_error)
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        self.controller = None;
+        Ok(())
+    }
+}
+
+fn ws281x_error(e: rs_ws281x::WS2811Error) -> io::Error {
+    io::Error::other(e.to_string())
+}
+
+impl From<StripType> for rs_ws281x::StripType {
+    fn from(strip_type: StripType) -> Self {
+        match strip_type {
+            StripType::Ws2811Rgb => rs_ws281x::StripType::Ws2811Rgb,
+            StripType::Ws2811Rbg => rs_ws281x::StripType::Ws2811Rbg,
+            StripType::Ws2811Grb => rs_ws281x::StripType::Ws2811Grb,
+            StripType::Ws2811Gbr => rs_ws281x::StripType::Ws2811Gbr,
+            StripType::Ws2811Brg => rs_ws281x::StripType::Ws2811Brg,
+            StripType::Ws2811Bgr => rs_ws281x::StripType::Ws2811Bgr,
+            StripType::Sk6812Rgbw => rs_ws281x::StripType::Sk6812Rgbw,
+            StripType::Sk6812Grbw => rs_ws281x::StripType::Sk6812Grbw,
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+use clap::ValueEnum;
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    seq::SliceRandom,
+};
+use serde::Deserialize;
+
+use crate::transition::TransitionSettings;
+
+/// How the playlist picks its next entry.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PlaylistOrder {
+    /// Top to bottom, then around again.
+    Sequential,
+    /// Every entry once in a random order, reshuffled each time around.
+    Shuffle,
+    /// An entry picked at random by weight each time, never the same one
+    /// twice in a row.
+    Random,
+}
+
+#[derive(Clone)]
+pub struct PlaylistEntry {
+    /// Index of the effect in the registry.
+    pub effect: usize,
+    pub duration: Duration,
+    pub weight: f32,
+    /// Overrides the global transition when switching to this entry.
+    pub transition: Option<TransitionSettings>,
+}
+
+/// Rotates through a list of effects on a timer.
+#[derive(Clone)]
+pub struct Playlist {
+    entries: Vec<PlaylistEntry>,
+    order: PlaylistOrder,
+    /// Entry indices in play order for the current pass, for `Shuffle`.
+    queue: Vec<usize>,
+    current: usize,
+    elapsed: Duration,
+}
+
+impl Playlist {
+    /// `entries` must not be empty.
+    pub fn new(entries: Vec<PlaylistEntry>, order: PlaylistOrder) -> Self {
+        let mut playlist = Playlist {
+            entries,
+            order,
+            queue: Vec::new(),
+            current: 0,
+            elapsed: Duration::ZERO,
+        };
+        playlist.current = playlist.pick_next(None);
+        playlist
+    }
+
+    pub fn current(&self) -> &PlaylistEntry {
+        &self.entries[self.current]
+    }
+
+    /// Counts `delta` against the current entry, returning the next entry
+    /// once the current one's time is up.
+    pub fn advance(&mut self, delta: Duration) -> Option<&PlaylistEntry> {
+        self.elapsed += delta;
+        if self.elapsed < self.current().duration {
+            return None;
+        }
+
+        self.elapsed = Duration::ZERO;
+        self.current = self.pick_next(Some(self.current));
+        Some(self.current())
+    }
+
+    fn pick_next(&mut self, previous: Option<usize>) -> usize {
+        let mut rng = rand::thread_rng();
+        let len = self.entries.len();
+        match self.order {
+            PlaylistOrder::Sequential => previous.map_or(0, |i| (i + 1) % len),
+            PlaylistOrder::Shuffle => {
+                if self.queue.is_empty() {
+                    self.queue.extend(0..len);
+                    self.queue.shuffle(&mut rng);
+                    // entries play from the back, so don't let a reshuffle
+                    // repeat the entry that just finished
+                    if len > 1 && self.queue.last() == previous.as_ref() {
+                        self.queue.swap(0, len - 1);
+                    }
+                }
+                self.queue.pop().unwrap()
+            }
+            PlaylistOrder::Random => {
+                let weights = self.entries.iter().enumerate().map(|(i, entry)| {
+                    if Some(i) == previous && len > 1 {
+                        0.0
+                    } else {
+                        entry.weight
+                    }
+                });
+                match WeightedIndex::new(weights) {
+                    Ok(dist) => dist.sample(&mut rng),
+                    // nothing else has any weight
+                    Err(_) => previous.unwrap_or(0),
+                }
+            }
+        }
+    }
+}
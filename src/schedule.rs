@@ -0,0 +1,169 @@
+use chrono::{Datelike, Local, NaiveDateTime, NaiveTime, TimeDelta, Weekday};
+
+/// One time window and what should change while it's active.
+#[derive(Clone)]
+pub struct ScheduleRule {
+    /// Days the window starts on. A window that runs past midnight keeps
+    /// going into the next morning.
+    pub days: Vec<Weekday>,
+    pub from: NaiveTime,
+    /// End of the window, exclusive. Equal to `from` for all day.
+    pub to: NaiveTime,
+    /// Registry index of the effect to show.
+    pub effect: Option<usize>,
+    /// Global brightness, 0 to 1.
+    pub brightness: Option<f32>,
+    pub off: Option<bool>,
+}
+
+/// What the active rules ask for at a given moment. `None` fields are left
+/// to the rest of the config.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct ScheduleState {
+    pub effect: Option<usize>,
+    pub brightness: Option<f32>,
+    pub off: bool,
+}
+
+/// Rules checked against the local clock. Where active rules disagree, the
+/// one listed last wins.
+#[derive(Clone)]
+pub struct Schedule {
+    rules: Vec<ScheduleRule>,
+    /// Added to the real clock, so `--now` can start the schedule at any
+    /// time and let it run on from there.
+    offset: TimeDelta,
+}
+
+impl Schedule {
+    pub fn new(rules: Vec<ScheduleRule>, now: Option<NaiveDateTime>) -> Self {
+        let offset = now
+            .map(|now| now - Local::now().naive_local())
+            .unwrap_or_default();
+        Schedule { rules, offset }
+    }
+
+    pub fn now(&self) -> NaiveDateTime {
+        Local::now().naive_local() + self.offset
+    }
+
+    pub fn state(&self) -> ScheduleState {
+        self.state_at(self.now())
+    }
+
+    pub fn state_at(&self, at: NaiveDateTime) -> ScheduleState {
+        let mut state = ScheduleState::default();
+        for rule in self.rules.iter().filter(|rule| rule.covers(at)) {
+            state.effect = rule.effect.or(state.effect);
+            state.brightness = rule.brightness.or(state.brightness);
+            state.off = rule.off.unwrap_or(state.off);
+        }
+        state
+    }
+}
+
+impl ScheduleRule {
+    fn covers(&self, at: NaiveDateTime) -> bool {
+        let time = at.time();
+        let today = at.weekday();
+        if self.from < self.to {
+            self.days.contains(&today) && self.from <= time && time < self.to
+        } else if self.from > self.to {
+            (self.days.contains(&today) && time >= self.from)
+                || (self.days.contains(&today.pred()) && time < self.to)
+        } else {
+            self.days.contains(&today)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn rule(days: &[Weekday], from: (u32, u32), to: (u32, u32)) -> ScheduleRule {
+        ScheduleRule {
+            days: days.to_vec(),
+            from: NaiveTime::from_hms_opt(from.0, from.1, 0).unwrap(),
+            to: NaiveTime::from_hms_opt(to.0, to.1, 0).unwrap(),
+            effect: None,
+            brightness: None,
+            off: None,
+        }
+    }
+
+    /// 2024-01-01 was a Monday.
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn a_window_covers_its_days_from_start_to_end() {
+        let rule = rule(&[Weekday::Mon], (9, 0), (17, 0));
+        assert!(!rule.covers(at(1, 8, 59)));
+        assert!(rule.covers(at(1, 9, 0)));
+        assert!(rule.covers(at(1, 16, 59)));
+        assert!(!rule.covers(at(1, 17, 0)));
+        assert!(!rule.covers(at(2, 12, 0)));
+    }
+
+    #[test]
+    fn a_window_past_midnight_counts_against_the_day_it_started() {
+        let rule = rule(&[Weekday::Mon], (22, 0), (2, 0));
+        assert!(!rule.covers(at(1, 1, 0)));
+        assert!(rule.covers(at(1, 23, 0)));
+        assert!(rule.covers(at(2, 1, 59)));
+        assert!(!rule.covers(at(2, 2, 0)));
+        assert!(!rule.covers(at(2, 23, 0)));
+
+        // Sunday night runs into Monday morning, across the week's end
+        let rule = self::rule(&[Weekday::Sun], (22, 0), (2, 0));
+        assert!(rule.covers(at(1, 1, 0)));
+        assert!(rule.covers(at(7, 22, 0)));
+    }
+
+    #[test]
+    fn equal_ends_cover_the_whole_day() {
+        let rule = rule(&[Weekday::Tue], (6, 0), (6, 0));
+        assert!(!rule.covers(at(1, 23, 59)));
+        assert!(rule.covers(at(2, 0, 0)));
+        assert!(rule.covers(at(2, 5, 59)));
+        assert!(rule.covers(at(2, 23, 59)));
+        assert!(!rule.covers(at(3, 0, 0)));
+    }
+
+    #[test]
+    fn the_last_matching_rule_wins() {
+        let mut evening = rule(&[Weekday::Mon], (18, 0), (23, 0));
+        evening.effect = Some(1);
+        evening.brightness = Some(0.5);
+        let mut late = rule(&[Weekday::Mon], (21, 0), (23, 0));
+        late.brightness = Some(0.2);
+        let mut dark = rule(&[Weekday::Mon], (22, 0), (23, 0));
+        dark.off = Some(true);
+        let mut lit = rule(&[Weekday::Mon], (22, 30), (23, 0));
+        lit.off = Some(false);
+        let schedule = Schedule::new(vec![evening, late, dark, lit], None);
+
+        assert_eq!(schedule.state_at(at(1, 12, 0)), ScheduleState::default());
+        let state = |hour, minute| schedule.state_at(at(1, hour, minute));
+        assert_eq!(
+            state(20, 0),
+            ScheduleState {
+                effect: Some(1),
+                brightness: Some(0.5),
+                off: false,
+            }
+        );
+        // a later rule overrides only the fields it sets
+        assert_eq!(state(21, 0).brightness, Some(0.2));
+        assert_eq!(state(21, 0).effect, Some(1));
+        assert!(state(22, 0).off);
+        assert!(!state(22, 30).off);
+    }
+}
@@ -0,0 +1,27 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Flips once the process receives SIGINT or SIGTERM, so render loops can
+/// exit and blank the strip instead of leaving the last frame latched.
+#[derive(Clone)]
+pub struct ShutdownSignal {
+    requested: Arc<AtomicBool>,
+}
+
+impl ShutdownSignal {
+    /// Installs the process-wide signal handler. Can only be called once.
+    pub fn install() -> Result<Self, String> {
+        let requested = Arc::new(AtomicBool::new(false));
+        let handler_flag = requested.clone();
+        ctrlc::set_handler(move || handler_flag.store(true, Ordering::SeqCst))
+            .map_err(|e| format!("failed to install signal handler: {}", e))?;
+
+        Ok(ShutdownSignal { requested })
+    }
+
+    pub fn requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+}
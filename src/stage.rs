@@ -0,0 +1,142 @@
+use std::time::{Duration, Instant};
+
+use sled::{color::Srgb, driver::Driver, Sled};
+
+use crate::effects::Effect;
+use crate::transition::{Transition, TransitionSettings};
+
+/// Owns a driver for every effect and the one layout they share, and decides
+/// what the strip shows: the current effect, or a blend while switching.
+pub struct Stage {
+    drivers: Vec<(Effect, Driver)>,
+    current: usize,
+    /// The effect being switched away from, still running on its own copy of
+    /// the layout until the transition finishes.
+    outgoing: Option<(usize, Transition)>,
+    transition: TransitionSettings,
+    last_step: Instant,
+}
+
+impl Stage {
+    /// Mounts `sled` onto the driver at index `first`.
+    pub fn new(
+        sled: Sled,
+        mut drivers: Vec<(Effect, Driver)>,
+        first: usize,
+        transition: TransitionSettings,
+    ) -> Self {
+        drivers[first].1.mount(sled);
+        Stage {
+            drivers,
+            current: first,
+            outgoing: None,
+            transition,
+            last_step: Instant::now(),
+        }
+    }
+
+    #[cfg_attr(not(feature = "tui"), allow(dead_code))]
+    pub fn effects(&self) -> impl Iterator<Item = &Effect> {
+        self.drivers.iter().map(|(effect, _)| effect)
+    }
+
+    #[cfg_attr(not(feature = "tui"), allow(dead_code))]
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn current_effect(&self) -> &Effect {
+        &self.drivers[self.current].0
+    }
+
+    pub fn current_driver(&self) -> &Driver {
+        &self.drivers[self.current].1
+    }
+
+    pub fn current_driver_mut(&mut self) -> (&Effect, &mut Driver) {
+        let (effect, driver) = &mut self.drivers[self.current];
+        (effect, driver)
+    }
+
+    #[cfg_attr(not(feature = "tui"), allow(dead_code))]
+    pub fn is_transitioning(&self) -> bool {
+        self.outgoing.is_some()
+    }
+
+    /// Starts a transition to the effect at `index`. Switching again before
+    /// a transition finishes drops the older effect straight away.
+    pub fn switch_to(&mut self, index: usize) {
+        self.switch_with(index, self.transition);
+    }
+
+    /// Like [`Stage::switch_to`], with a transition other than the default.
+    pub fn switch_with(&mut self, index: usize, transition: TransitionSettings) {
+        if index == self.current {
+            return;
+        }
+        self.finish_transition();
+
+        let sled = self.drivers[self.current].1.sled().unwrap().clone();
+        self.drivers[index].1.mount(sled);
+
+        if transition.duration.is_zero() {
+            self.drivers[self.current].1.dismount();
+        } else {
+            let transition = Transition::new(&transition, &self.drivers[index].1);
+            self.outgoing = Some((self.current, transition));
+        }
+        self.current = index;
+    }
+
+    /// Advances every running effect, by `fixed` if given or by the time
+    /// since the last step otherwise. Returns how far it advanced.
+    pub fn step(&mut self, fixed: Option<Duration>) -> Duration {
+        let delta = fixed.unwrap_or_else(|| self.last_step.elapsed());
+        self.last_step = Instant::now();
+
+        let running = self.outgoing.as_ref().map(|(i, _)| *i);
+        for index in [Some(self.current), running].into_iter().flatten() {
+            match fixed {
+                Some(period) => self.drivers[index].1.step_by(period),
+                None => self.drivers[index].1.step(),
+            }
+        }
+
+        if let Some((_, transition)) = &mut self.outgoing {
+            transition.advance(delta);
+            if transition.is_done() {
+                self.finish_transition();
+            }
+        }
+        delta
+    }
+
+    /// Fills `frame` with what the strip should show right now.
+    pub fn render(&self, frame: &mut Vec<Srgb>) {
+        let current = &self.drivers[self.current].1;
+        match &self.outgoing {
+            Some((outgoing, transition)) => {
+                transition.blend(&self.drivers[*outgoing].1, current, frame)
+            }
+            None => {
+                frame.clear();
+                frame.extend(current.colors().copied());
+            }
+        }
+    }
+
+    /// Swaps a new layout into the current effect, re-running its startup.
+    /// Any transition in progress is cut short.
+    pub fn remount(&mut self, sled: Sled) {
+        self.finish_transition();
+        let driver = &mut self.drivers[self.current].1;
+        driver.dismount();
+        driver.mount(sled);
+    }
+
+    fn finish_transition(&mut self) {
+        if let Some((outgoing, _)) = self.outgoing.take() {
+            self.drivers[outgoing].1.dismount();
+        }
+    }
+}
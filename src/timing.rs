@@ -0,0 +1,171 @@
+use std::{
+    fmt, thread,
+    time::{Duration, Instant},
+};
+
+use crate::output::PowerReport;
+
+/// Sleeps the render loop so it runs at a target frame rate instead of
+/// spinning a core as fast as it can.
+pub struct FramePacer {
+    period: Option<Duration>,
+    next_frame: Instant,
+}
+
+impl FramePacer {
+    /// A pacer for `fps` frames per second, or one that never sleeps if `None`.
+    pub fn new(fps: Option<f32>) -> Self {
+        FramePacer {
+            period: fps.map(|fps| Duration::from_secs_f32(1.0 / fps)),
+            next_frame: Instant::now(),
+        }
+    }
+
+    pub fn period(&self) -> Option<Duration> {
+        self.period
+    }
+
+    /// Sleeps until the next frame is due. Returns `true` if the deadline had
+    /// already passed, in which case the schedule restarts from now rather
+    /// than rushing to catch up.
+    pub fn wait(&mut self) -> bool {
+        let Some(period) = self.period else {
+            return false;
+        };
+
+        self.next_frame += period;
+        let now = Instant::now();
+        if now < self.next_frame {
+            thread::sleep(self.next_frame - now);
+            false
+        } else {
+            self.next_frame = now;
+            true
+        }
+    }
+}
+
+/// Collects per-frame work times and summarizes them once per interval.
+pub struct FrameStats {
+    interval: Duration,
+    window_start: Instant,
+    frame_times: Vec<Duration>,
+    dropped: usize,
+    power: Option<PowerSummary>,
+}
+
+impl FrameStats {
+    pub fn new(interval: Duration) -> Self {
+        FrameStats {
+            interval,
+            window_start: Instant::now(),
+            frame_times: Vec::new(),
+            dropped: 0,
+            power: None,
+        }
+    }
+
+    /// Records how long one frame took to step, render and output.
+    pub fn record(&mut self, frame_time: Duration) {
+        self.frame_times.push(frame_time);
+    }
+
+    /// Records a frame that missed its deadline.
+    pub fn record_dropped(&mut self) {
+        self.dropped += 1;
+    }
+
+    /// Records the power limiter's verdict on one frame.
+    pub fn record_power(&mut self, report: &PowerReport) {
+        let power = self.power.get_or_insert(PowerSummary {
+            peak_ma: 0.0,
+            min_scale: 1.0,
+            limited_frames: 0,
+        });
+        power.peak_ma = power.peak_ma.max(report.estimated_ma);
+        power.min_scale = power.min_scale.min(report.scale);
+        if report.scale < 1.0 {
+            power.limited_frames += 1;
+        }
+    }
+
+    /// Returns a summary and starts a new window once `interval` has passed.
+    pub fn take_summary(&mut self) -> Option<FrameSummary> {
+        let elapsed = self.window_start.elapsed();
+        if elapsed < self.interval || self.frame_times.is_empty() {
+            return None;
+        }
+
+        self.frame_times.sort_unstable();
+        let count = self.frame_times.len();
+        let total: Duration = self.frame_times.iter().sum();
+        let p99_index = ((count as f32 * 0.99).ceil() as usize).clamp(1, count) - 1;
+
+        let summary = FrameSummary {
+            hz: count as f32 / elapsed.as_secs_f32(),
+            min: self.frame_times[0],
+            avg: total / count as u32,
+            max: self.frame_times[count - 1],
+            p99: self.frame_times[p99_index],
+            dropped: self.dropped,
+            power: self.power.take(),
+        };
+
+        self.frame_times.clear();
+        self.dropped = 0;
+        self.window_start = Instant::now();
+        Some(summary)
+    }
+}
+
+pub struct FrameSummary {
+    pub hz: f32,
+    pub min: Duration,
+    pub avg: Duration,
+    pub max: Duration,
+    pub p99: Duration,
+    pub dropped: usize,
+    pub power: Option<PowerSummary>,
+}
+
+pub struct PowerSummary {
+    /// Highest estimated draw of any frame before limiting.
+    pub peak_ma: f32,
+    /// Strongest dimming applied to any frame.
+    pub min_scale: f32,
+    pub limited_frames: usize,
+}
+
+impl fmt::Display for FrameSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Running at {:.1} Hz (frame min {:.2} ms, avg {:.2} ms, max {:.2} ms, p99 {:.2} ms, {} dropped).",
+            self.hz,
+            millis(self.min),
+            millis(self.avg),
+            millis(self.max),
+            millis(self.p99),
+            self.dropped
+        )?;
+
+        if let Some(power) = &self.power {
+            write!(f, " Power peak {:.2} A", power.peak_ma / 1000.0)?;
+            if power.limited_frames > 0 {
+                write!(
+                    f,
+                    ", limited on {} frames (down to {:.0}%).",
+                    power.limited_frames,
+                    power.min_scale * 100.0
+                )?;
+            } else {
+                write!(f, ", within budget.")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn millis(duration: Duration) -> f32 {
+    duration.as_secs_f32() * 1000.0
+}
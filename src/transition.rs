@@ -0,0 +1,118 @@
+use std::time::Duration;
+
+use clap::ValueEnum;
+use serde::Deserialize;
+use sled::{color::Srgb, driver::Driver};
+
+/// Width of the soft edge on the wipe and radial curves, as a fraction of the
+/// strip or radius.
+const EDGE: f32 = 0.1;
+
+/// How the incoming effect takes over from the outgoing one.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TransitionCurve {
+    /// Every LED fades at a constant rate.
+    Linear,
+    /// Every LED fades, slowly at the start and end and quickly in between.
+    Ease,
+    /// A soft edge sweeps along the strip from the first LED to the last.
+    Wipe,
+    /// A soft-edged ring grows outward from the layout's center point.
+    Radial,
+}
+
+#[derive(Clone, Copy)]
+pub struct TransitionSettings {
+    pub curve: TransitionCurve,
+    /// How long a transition lasts. Zero switches effects instantly.
+    pub duration: Duration,
+}
+
+impl Default for TransitionSettings {
+    fn default() -> Self {
+        TransitionSettings {
+            curve: TransitionCurve::Ease,
+            duration: Duration::from_secs(1),
+        }
+    }
+}
+
+/// A blend in progress from one driver's colors to another's.
+pub struct Transition {
+    curve: TransitionCurve,
+    duration: Duration,
+    elapsed: Duration,
+    /// Per-LED point in the transition, 0 to 1, at which the wipe and radial
+    /// curves reach that LED.
+    keys: Vec<f32>,
+}
+
+impl Transition {
+    /// Starts a transition onto `incoming`, which must already be mounted.
+    pub fn new(settings: &TransitionSettings, incoming: &Driver) -> Self {
+        let keys = match (settings.curve, incoming.sled()) {
+            (TransitionCurve::Wipe, Some(sled)) => {
+                let last = sled.num_leds().saturating_sub(1).max(1) as f32;
+                (0..sled.num_leds()).map(|i| i as f32 / last).collect()
+            }
+            (TransitionCurve::Radial, Some(sled)) => {
+                let center = sled.center_point();
+                let distances: Vec<f32> = incoming
+                    .positions()
+                    .map(|pos| pos.distance(center))
+                    .collect();
+                let furthest = distances.iter().copied().fold(f32::EPSILON, f32::max);
+                distances.iter().map(|d| d / furthest).collect()
+            }
+            _ => Vec::new(),
+        };
+
+        Transition {
+            curve: settings.curve,
+            duration: settings.duration,
+            elapsed: Duration::ZERO,
+            keys,
+        }
+    }
+
+    pub fn advance(&mut self, delta: Duration) {
+        self.elapsed += delta;
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    /// Fills `frame` with `outgoing`'s colors blended into `incoming`'s.
+    pub fn blend(&self, outgoing: &Driver, incoming: &Driver, frame: &mut Vec<Srgb>) {
+        let t = if self.duration.is_zero() {
+            1.0
+        } else {
+            (self.elapsed.as_secs_f32() / self.duration.as_secs_f32()).min(1.0)
+        };
+
+        frame.clear();
+        let pairs = outgoing.colors().zip(incoming.colors());
+        frame.extend(pairs.enumerate().map(|(i, (from, to))| {
+            let mix = self.mix(i, t);
+            Srgb::new(
+                from.red + (to.red - from.red) * mix,
+                from.green + (to.green - from.green) * mix,
+                from.blue + (to.blue - from.blue) * mix,
+            )
+        }));
+    }
+
+    /// How much of the incoming effect LED `index` shows at progress `t`.
+    fn mix(&self, index: usize, t: f32) -> f32 {
+        match self.curve {
+            TransitionCurve::Linear => t,
+            TransitionCurve::Ease => t * t * (3.0 - 2.0 * t),
+            TransitionCurve::Wipe | TransitionCurve::Radial => {
+                let key = self.keys.get(index).copied().unwrap_or(0.0);
+                ((t * (1.0 + EDGE) - key) / EDGE).clamp(0.0, 1.0)
+            }
+        }
+    }
+}
@@ -1,11 +1,10 @@
 use std::{
-    collections::HashMap,
     io::{stdout, Stdout},
     time::Instant,
 };
 
 use crossterm::{
-    event::{self, KeyCode, KeyEventKind},
+    event::{self, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
     terminal::{enable_raw_mode, EnterAlternateScreen},
     ExecutableCommand,
 };
@@ -14,88 +13,78 @@ use ratatui::{
     prelude::{CrosstermBackend, Stylize, Terminal, *},
     style::{Color, Style},
     widgets::{
-        canvas::{Canvas, Circle, Shape},
-        Block, Borders, List, ListDirection, ListState,
+        canvas::{Canvas, Shape},
+        Block, Borders, List, ListDirection, ListState, Paragraph, Wrap,
     },
 };
 
-use sled::{driver::Driver, Sled};
+use sled::Sled;
 use symbols::Marker;
 
+use crate::effects::Effect;
+use crate::output::to_rgb8;
+use crate::stage::Stage;
+
+#[derive(Default)]
 enum SelectableWidget {
+    #[default]
     Effects,
     Settings,
 }
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy)]
-pub enum Effect {
-    Comet,
-    Ripples,
-    Warpspeed,
-}
-
-impl Effect {
-    pub fn as_str(&self) -> &str {
-        match self {
-            Effect::Comet => "Comet",
-            Effect::Ripples => "Ripples",
-            Effect::Warpspeed => "Warpspeed",
-        }
-    }
-}
-
-impl Default for SelectableWidget {
-    fn default() -> Self {
-        SelectableWidget::Effects
-    }
-}
-
 pub struct App {
     should_quit: bool,
     should_pause: bool,
     selected_widget: SelectableWidget,
     terminal: Terminal<CrosstermBackend<Stdout>>,
-    pub drivers: HashMap<Effect, Driver>,
-    pub current_effect: Effect,
+    stage: Stage,
     last_draw: Instant,
-    positions: Vec<sled::Vec2>,
 
     /* effects widget */
     effects_list_state: ListState,
+
+    /* settings widget */
+    settings_list_state: ListState,
+    /// What's been typed so far when entering a parameter value directly.
+    param_input: Option<String>,
+    settings_error: Option<String>,
 }
 
 impl App {
-    pub fn new(sled: Sled, mut drivers: HashMap<Effect, Driver>) -> Self {
+    /// Takes over the terminal to show and control `stage`.
+    pub fn new(stage: Stage) -> Self {
         stdout().execute(EnterAlternateScreen).unwrap();
         enable_raw_mode().unwrap();
         let mut terminal = Terminal::new(CrosstermBackend::new(stdout())).unwrap();
         terminal.clear().unwrap();
 
         let mut effects_list_state = ListState::default();
-        effects_list_state.select(Some(0));
+        effects_list_state.select(Some(stage.current()));
+
+        let mut settings_list_state = ListState::default();
+        settings_list_state.select(first_param(stage.current_effect()));
 
-        let first_effect = drivers.keys().into_iter().next().unwrap().clone();
-        let first_driver = drivers.get_mut(&first_effect).unwrap();
-        first_driver.mount(sled);
-        let positions = first_driver.positions().collect();
         App {
             should_quit: false,
             should_pause: false,
             selected_widget: SelectableWidget::Effects,
             terminal,
-            drivers,
-            current_effect: first_effect,
-            positions,
+            stage,
             effects_list_state,
+            settings_list_state,
+            param_input: None,
+            settings_error: None,
             last_draw: Instant::now(),
         }
     }
 
-    pub fn heartbeat(&mut self) -> std::io::Result<()> {
+    /// Handles input, redraws if it's time, and steps the effect by `step`,
+    /// or by the time since the last step if `None`.
+    pub fn heartbeat(&mut self, step: Option<std::time::Duration>) -> std::io::Result<()> {
         if event::poll(std::time::Duration::from_nanos(50))? {
             if let event::Event::Key(key) = event::read()? {
                 if key.kind == KeyEventKind::Press {
-                    self.handle_input(key.code);
+                    self.handle_input(key);
                 }
             }
         }
@@ -106,13 +95,16 @@ impl App {
         }
 
         if !self.should_pause {
-            self.drivers.get_mut(&self.current_effect).unwrap().step();
+            self.stage.step(step);
         }
 
         Ok(())
     }
 
     pub fn draw(&mut self) -> std::io::Result<()> {
+        let mut colors = Vec::new();
+        self.stage.render(&mut colors);
+
         self.terminal.draw(|frame| {
             /* variables */
             let layout = Layout::default()
@@ -125,10 +117,9 @@ impl App {
                 .split(frame.area());
 
             let items = self
-                .drivers
-                .keys()
-                .into_iter()
-                .map(|effect_enum| effect_enum.as_str())
+                .stage
+                .effects()
+                .map(|effect| effect.name)
                 .collect::<Vec<&str>>();
 
             /* effects selector */
@@ -170,17 +161,19 @@ impl App {
 
             /* visualizer */
 
-            let effect = self.current_effect.as_str();
+            let effect = self.stage.current_effect();
+            let current_driver = self.stage.current_driver();
 
             let running_state = if self.should_pause {
                 "PAUSED"
+            } else if self.stage.is_transitioning() {
+                "SWITCHING"
             } else {
                 "RUNNING"
             };
 
-            let visualizer_title = format!(" {} [{}] ", effect, running_state);
+            let visualizer_title = format!(" {} [{}] ", effect.name, running_state);
 
-            let current_driver = &self.drivers[&self.current_effect];
             let sled = current_driver.sled().unwrap();
             let domain = sled.domain();
             let center = sled.center_point();
@@ -205,11 +198,12 @@ impl App {
                     color: Color::Rgb(128, 128, 128),
                 });
 
-                for (col, pos) in current_driver.colors_and_positions_coerced::<u8>() {
+                for (col, pos) in colors.iter().zip(current_driver.positions()) {
+                    let [r, g, b] = to_rgb8(col);
                     ctx.draw(&Point {
                         x: pos.x,
                         y: pos.y,
-                        color: Color::Rgb(col.red, col.green, col.blue),
+                        color: Color::Rgb(r, g, b),
                     });
                 }
             });
@@ -217,12 +211,55 @@ impl App {
             frame.render_widget(canvas, layout[1]);
 
             /* settings panel */
-            let settings_title_style = if let SelectableWidget::Settings = self.selected_widget {
-                Style::default().reversed()
-            } else {
-                Style::default()
+            let selected_param = self.settings_list_state.selected();
+            let params = effect
+                .params
+                .iter()
+                .enumerate()
+                .map(|(i, p)| match &self.param_input {
+                    Some(input) if selected_param == Some(i) => format!("{} = {}_", p.name, input),
+                    _ => {
+                        let value = effect
+                            .get_param(current_driver, p.name)
+                            .unwrap_or(p.default);
+                        format!("{} = {}", p.name, p.format(value))
+                    }
+                })
+                .collect::<Vec<String>>();
+
+            let settings_layout = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(0), Constraint::Length(5)])
+                .split(Block::new().borders(Borders::ALL).inner(layout[2]));
+
+            let (settings_title_style, param_highlight_style) =
+                if let SelectableWidget::Settings = self.selected_widget {
+                    (Style::default().reversed(), Style::default().reversed())
+                } else {
+                    (Style::default(), Style::default().bold())
+                };
+
+            let list = List::new(params)
+                .highlight_style(param_highlight_style)
+                .highlight_symbol(" ")
+                .repeat_highlight_symbol(true);
+
+            frame.render_stateful_widget(list, settings_layout[0], &mut self.settings_list_state);
+
+            let footer = match (&self.settings_error, selected_param) {
+                (Some(e), _) => Paragraph::new(e.as_str()).red(),
+                (None, Some(i)) => {
+                    let p = &effect.params[i];
+                    Paragraph::new(format!(
+                        "{} [{}..={}]\n←/→ adjust, type a value, Esc back",
+                        p.description, p.min, p.max
+                    ))
+                }
+                (None, None) => Paragraph::new("This effect has no parameters."),
             };
 
+            frame.render_widget(footer.wrap(Wrap { trim: true }), settings_layout[1]);
+
             frame.render_widget(
                 Block::new()
                     .borders(Borders::ALL)
@@ -244,10 +281,22 @@ impl App {
         self.should_pause
     }
 
-    fn handle_input(&mut self, key_code: KeyCode) {
-        if key_code == event::KeyCode::Char('q') {
+    pub fn stage(&self) -> &Stage {
+        &self.stage
+    }
+
+    /// Swaps a new layout into the running effect, re-running its startup.
+    pub fn remount(&mut self, sled: Sled) {
+        self.stage.remount(sled);
+    }
+
+    fn handle_input(&mut self, key: KeyEvent) {
+        // raw mode swallows SIGINT, so treat ctrl+c like any other quit key
+        let ctrl_c =
+            key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+        let key_code = key.code;
+        if key_code == KeyCode::Char('q') || ctrl_c {
             self.should_quit = true;
-            return;
         } else {
             match self.selected_widget {
                 SelectableWidget::Effects => self.handle_input_effects(key_code),
@@ -261,55 +310,112 @@ impl App {
             KeyCode::Down => {
                 self.should_pause = true;
                 self.effects_list_state.select(Some(
-                    (self.effects_list_state.selected().unwrap() + 1) % self.drivers.len(),
+                    (self.effects_list_state.selected().unwrap() + 1)
+                        % self.stage.effects().count(),
                 ))
             }
             KeyCode::Up => {
                 self.should_pause = true;
+                let len = self.stage.effects().count();
                 self.effects_list_state.select(Some(
-                    (self.effects_list_state.selected().unwrap() - 1) % self.drivers.len(),
+                    (self.effects_list_state.selected().unwrap() + len - 1) % len,
                 ))
             }
             KeyCode::Enter => {
                 if let Some(e) = self.effects_list_state.selected() {
-                    let old_effect = self.current_effect;
-
-                    let effects = self.drivers.keys().into_iter().collect::<Vec<&Effect>>();
-                    self.current_effect = *effects[e];
-
                     // handling if they hit enter on their current selection
-                    if self.current_effect == old_effect {
-                        if self.should_pause == false {
-                            self.should_pause = true;
-                            return;
-                        } else {
-                            self.should_pause = false;
-                            return;
-                        }
+                    if e == self.stage.current() {
+                        self.should_pause = !self.should_pause;
+                        return;
                     }
 
-                    let old_driver = self.drivers.get_mut(&old_effect).unwrap();
-                    let sled = old_driver.dismount();
-                    let new_driver = self.drivers.get_mut(&self.current_effect).unwrap();
-                    new_driver.mount(sled);
+                    self.stage.switch_to(e);
+                    self.settings_list_state
+                        .select(first_param(self.stage.current_effect()));
+                    self.param_input = None;
+                    self.settings_error = None;
 
                     self.selected_widget = SelectableWidget::Settings;
                     self.should_pause = false;
                 }
             }
 
-            KeyCode::Right => self.selected_widget = SelectableWidget::Settings,
+            KeyCode::Right | KeyCode::Tab => self.selected_widget = SelectableWidget::Settings,
 
             _ => {}
         }
     }
 
     fn handle_input_settings(&mut self, key_code: KeyCode) {
+        self.settings_error = None;
+        let Some(selected) = self.settings_list_state.selected() else {
+            if matches!(key_code, KeyCode::Left | KeyCode::Esc | KeyCode::Tab) {
+                self.selected_widget = SelectableWidget::Effects;
+            }
+            return;
+        };
+
+        // typing a value takes over the keys until it's applied or dropped
+        if let Some(input) = &mut self.param_input {
+            match key_code {
+                KeyCode::Char(c) if is_number_char(c) => input.push(c),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Enter => {
+                    let input = self.param_input.take().unwrap_or_default();
+                    match input.parse::<f32>() {
+                        Ok(value) => self.set_param(selected, value),
+                        Err(_) => {
+                            self.settings_error = Some(format!("`{}` is not a number", input))
+                        }
+                    }
+                }
+                KeyCode::Esc => self.param_input = None,
+                _ => {}
+            }
+            return;
+        }
+
+        let len = self.stage.current_effect().params.len();
         match key_code {
-            KeyCode::Left => self.selected_widget = SelectableWidget::Effects,
+            KeyCode::Down => self.settings_list_state.select(Some((selected + 1) % len)),
+            KeyCode::Up => self
+                .settings_list_state
+                .select(Some((selected + len - 1) % len)),
+            KeyCode::Right => self.nudge_param(selected, 1.0),
+            KeyCode::Left => self.nudge_param(selected, -1.0),
+            KeyCode::Char(c) if is_number_char(c) => self.param_input = Some(c.to_string()),
+            KeyCode::Esc | KeyCode::Tab => self.selected_widget = SelectableWidget::Effects,
             _ => {}
         }
     }
+
+    /// Moves the parameter at `index` one step up or down, stopping at the
+    /// ends of its range.
+    fn nudge_param(&mut self, index: usize, direction: f32) {
+        let effect = self.stage.current_effect();
+        let p = &effect.params[index];
+        let value = effect
+            .get_param(self.stage.current_driver(), p.name)
+            .unwrap_or(p.default);
+        self.set_param(index, (value + direction * p.step()).clamp(p.min, p.max));
+    }
+
+    fn set_param(&mut self, index: usize, value: f32) {
+        let (effect, driver) = self.stage.current_driver_mut();
+        if let Err(e) = effect.set_param(driver, effect.params[index].name, value) {
+            self.settings_error = Some(e);
+        }
+    }
+}
+
+fn first_param(effect: &Effect) -> Option<usize> {
+    (!effect.params.is_empty()).then_some(0)
+}
+
+fn is_number_char(c: char) -> bool {
+    c.is_ascii_digit() || c == '.' || c == '-'
 }
 
 struct Point {